@@ -1,18 +1,26 @@
-use clap::{Parser, Subcommand};
-use pg_extras::diagnose::report::render_diagnose_report;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgMatches, Args, Command, CommandFactory, FromArgMatches, Parser, Subcommand};
+use pg_extras::backends::backends_output;
+use pg_extras::diagnose::report::{
+    diagnose_output, render_diagnose_report, write_diagnose_report, write_junit_report,
+    write_sarif_report,
+};
+use pg_extras::diagnose::run::{run_diagnose, CheckResult, Severity};
 use pg_extras::{
-    all_locks, bloat, blocking, buffercache_stats, buffercache_usage, cache_hit, calls,
-    connections, db_settings, diagnose, duplicate_indexes, extensions, index_cache_hit,
-    index_scans, index_size, index_usage, indexes, locks, long_running_queries, mandelbrot,
-    null_indexes, outliers, pg_pool, records_rank, render_table, seq_scans, ssl_used,
-    table_cache_hit, table_index_scans, table_indexes_size, table_size, tables, total_index_size,
-    total_table_size, unused_indexes, vacuum_stats, AllLocks, Bloat, Blocking, BuffercacheStats,
-    BuffercacheUsage, CacheHit, Calls, Connections, DbSettings, DuplicateIndexes, Extensions,
-    IndexCacheHit, IndexScans, IndexSize, IndexUsage, Indexes, Locks, LongRunningQueries,
-    Mandelbrot, NullIndexes, Outliers, PgExtrasError, Query, RecordsRank, SeqScans, SslUsed,
-    TableCacheHit, TableIndexScans, TableIndexesSize, TableSize, Tables, TotalIndexSize,
-    TotalTableSize, UnusedIndexes, VacuumStats,
+    render_output, write_output, BackendFilter, BackendSignal, Config, ConnectionBuilder,
+    CustomQuery, DiagnoseConfig, OutputFormat, PgExtras, PgExtrasError, Protection, QueryKind,
+    QueryOutput, QueryParam, RateQuery, SharedStats, Snapshot, StatsReset, Timeouts, Watch,
 };
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -25,219 +33,1086 @@ https://github.com/pawurb/pg-extras-rs"
 pub struct PgExtrasArgs {
     #[command(subcommand)]
     pub cmd: PgSubcommand,
+    #[arg(
+        long,
+        global = true,
+        value_parser = humantime::parse_duration,
+        help = "Cancel queries running longer than this, e.g. 5s or 500ms"
+    )]
+    pub statement_timeout: Option<Duration>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Table,
+        help = "Output format, non-table formats contain raw values"
+    )]
+    pub format: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        help = "Run in every database of the cluster, adding a database column"
+    )]
+    pub all_databases: bool,
+    #[arg(
+        long,
+        global = true,
+        value_parser = humantime::parse_duration,
+        help = "Sample cumulative counters twice this far apart and report per-second rates, e.g. 30s"
+    )]
+    pub interval: Option<Duration>,
+    #[arg(
+        long,
+        global = true,
+        value_parser = humantime::parse_duration,
+        help = "Re-run the query on this interval, highlighting new and changed rows, e.g. 2s"
+    )]
+    pub watch: Option<Duration>,
+    #[arg(
+        long,
+        global = true,
+        help = "Profile from the config file, defaults to its default_profile"
+    )]
+    pub profile: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Config file, defaults to $PG_EXTRAS_CONFIG or ~/.config/pgextras/config.toml"
+    )]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
 }
 
-#[cfg(feature = "web")]
-use sqlx::PgPool;
+/// Overrides for the connection settings, see `ConnectionBuilder` for the resolution order.
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Connection")]
+pub struct ConnectionArgs {
+    #[arg(
+        long,
+        global = true,
+        help = "Connection URL, defaults to $PG_EXTRAS_DATABASE_URL or $DATABASE_URL"
+    )]
+    pub database_url: Option<String>,
+    #[arg(long, global = true, help = "Host name or Unix socket directory")]
+    pub host: Option<String>,
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    #[arg(long, global = true)]
+    pub user: Option<String>,
+    #[arg(long, global = true)]
+    pub dbname: Option<String>,
+    #[arg(long, global = true, help = "Service name from pg_service.conf")]
+    pub service: Option<String>,
+}
+
+impl ConnectionArgs {
+    #[cfg(feature = "web")]
+    fn is_empty(&self) -> bool {
+        self.database_url.is_none()
+            && self.host.is_none()
+            && self.port.is_none()
+            && self.user.is_none()
+            && self.dbname.is_none()
+            && self.service.is_none()
+    }
+
+    /// Applies the options passed on the command line on top of `builder`.
+    fn apply(&self, mut builder: ConnectionBuilder) -> ConnectionBuilder {
+        if let Some(database_url) = &self.database_url {
+            builder = builder.database_url(database_url);
+        }
+        if let Some(host) = &self.host {
+            builder = builder.host(host);
+        }
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let Some(user) = &self.user {
+            builder = builder.user(user);
+        }
+        if let Some(dbname) = &self.dbname {
+            builder = builder.dbname(dbname);
+        }
+        if let Some(service) = &self.service {
+            builder = builder.service(service);
+        }
+        builder
+    }
+}
 
 #[derive(Subcommand, Debug)]
 pub enum PgSubcommand {
     #[command(about = "Diagnose common database problems")]
-    Diagnose(EmptyArgs),
+    Diagnose(DiagnoseArgs),
+    #[command(about = "Cancel the current query of backends")]
+    Cancel(SignalArgs),
+    #[command(about = "Terminate the connection of backends")]
+    Terminate(SignalArgs),
+    #[command(about = "Reset statistics counters, e.g. before measuring a change")]
+    ResetStats(ResetStatsArgs),
+    #[command(about = "Capture the results of every query into a JSON file")]
+    Snapshot(SnapshotArgs),
+    #[command(about = "Show a query from a snapshot file, without connecting")]
+    Render(RenderArgs),
+    #[command(about = "Compare two snapshot files")]
+    Diff(DiffArgs),
+    #[cfg(feature = "tui")]
+    #[command(about = "Full-screen view of sessions, locks, statements and cache hit ratios")]
+    Top(TopArgs),
     #[cfg(feature = "web")]
     #[command(about = "Start dashboard web server")]
     Web(EmptyArgs),
-    #[command(about = &AllLocks::description())]
-    AllLocks(EmptyArgs),
-    #[command(about = &Bloat::description())]
-    Bloat(EmptyArgs),
-    #[command(about = &Blocking::description())]
-    Blocking(EmptyArgs),
-    #[command(about = &BuffercacheStats::description())]
-    BuffercacheStats(EmptyArgs),
-    #[command(about = &BuffercacheUsage::description())]
-    BuffercacheUsage(EmptyArgs),
-    #[command(about = &CacheHit::description())]
-    CacheHit(EmptyArgs),
-    #[command(about = &Calls::description())]
-    Calls(EmptyArgs),
-    #[command(about = &Connections::description())]
-    Connections(EmptyArgs),
-    #[command(about = &DbSettings::description())]
-    DbSettings(EmptyArgs),
-    #[command(about = &DuplicateIndexes::description())]
-    DuplicateIndexes(EmptyArgs),
-    #[command(about = &Extensions::description())]
-    Extensions(EmptyArgs),
-    #[command(about = &IndexCacheHit::description())]
-    IndexCacheHit(EmptyArgs),
-    #[command(about = &IndexScans::description())]
-    IndexScans(EmptyArgs),
-    #[command(about = &IndexSize::description())]
-    IndexSize(EmptyArgs),
-    #[command(about = &IndexUsage::description())]
-    IndexUsage(EmptyArgs),
-    #[command(about = &Indexes::description())]
-    Indexes(EmptyArgs),
-    #[command(about = &Locks::description())]
-    Locks(EmptyArgs),
-    #[command(about = &LongRunningQueries::description())]
-    LongRunningQueries(EmptyArgs),
-    #[command(about = &NullIndexes::description())]
-    NullIndexes(EmptyArgs),
-    #[command(about = &Outliers::description())]
-    Outliers(EmptyArgs),
-    #[command(about = &Mandelbrot::description())]
-    Mandelbrot(EmptyArgs),
-    #[command(about = &RecordsRank::description())]
-    RecordsRank(EmptyArgs),
-    #[command(about = &SeqScans::description())]
-    SeqScans(EmptyArgs),
-    #[command(about = &SslUsed::description())]
-    SslUsed(EmptyArgs),
-    #[command(about = &TableCacheHit::description())]
-    TableCacheHit(EmptyArgs),
-    #[command(about = &TableIndexScans::description())]
-    TableIndexScans(EmptyArgs),
-    #[command(about = &TableIndexesSize::description())]
-    TableIndexesSize(EmptyArgs),
-    #[command(about = &TableSize::description())]
-    TableSize(EmptyArgs),
-    #[command(about = &Tables::description())]
-    Tables(EmptyArgs),
-    #[command(about = &TotalIndexSize::description())]
-    TotalIndexSize(EmptyArgs),
-    #[command(about = &TotalTableSize::description())]
-    TotalTableSize(EmptyArgs),
-    #[command(about = &UnusedIndexes::description())]
-    UnusedIndexes(EmptyArgs),
-    #[command(about = &VacuumStats::description())]
-    VacuumStats(EmptyArgs),
+    #[command(flatten)]
+    Query(QueryCommand),
+    #[command(flatten)]
+    Custom(CustomCommand),
+}
+
+/// One subcommand per query in the registry, e.g. `pgextras cache-hit`.
+#[derive(Debug)]
+pub struct QueryCommand {
+    pub kind: QueryKind,
+    /// Values of the params passed on the command line, others use their defaults.
+    pub params: HashMap<QueryParam, String>,
+}
+
+fn subcommand_name(kind: &QueryKind) -> String {
+    kind.name().replace('_', "-")
+}
+
+fn param_arg(param: QueryParam) -> Arg {
+    let (flag, help) = match param {
+        QueryParam::Schema => ("schema", "Schema to inspect"),
+        QueryParam::Limit => ("limit", "Maximum number of returned rows"),
+        QueryParam::MinRelationSizeMb => ("min-size", "Skip relations smaller than this, in MB"),
+    };
+
+    Arg::new(param.name())
+        .long(flag)
+        .help(help)
+        .value_name(param.name().to_uppercase())
+        .default_value(param.default_value())
+        .value_parser(move |value: &str| {
+            param
+                .parse(Some(value.to_string()))
+                .map(|_| value.to_string())
+                .map_err(|_| format!("expected {}", param.expected()))
+        })
+}
+
+impl FromArgMatches for QueryCommand {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let Some((name, matches)) = matches.subcommand() else {
+            return Err(clap::Error::new(ErrorKind::MissingSubcommand));
+        };
+        let kind = QueryKind::ALL
+            .iter()
+            .find(|kind| subcommand_name(kind) == name)
+            .ok_or_else(|| clap::Error::new(ErrorKind::InvalidSubcommand))?;
+
+        let params = kind
+            .params()
+            .into_iter()
+            .filter_map(|param| {
+                // Defaults are left to the client, so a profile schema can apply.
+                if matches.value_source(param.name()) != Some(ValueSource::CommandLine) {
+                    return None;
+                }
+                let value = matches.get_one::<String>(param.name())?;
+                Some((param, value.clone()))
+            })
+            .collect();
+
+        Ok(Self {
+            kind: *kind,
+            params,
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Subcommand for QueryCommand {
+    fn augment_subcommands(cmd: Command) -> Command {
+        QueryKind::ALL.iter().fold(cmd, |cmd, kind| {
+            let subcommand = Command::new(subcommand_name(kind)).about(kind.description());
+            cmd.subcommand(subcommand.args(kind.params().into_iter().map(param_arg)))
+        })
+    }
+
+    fn augment_subcommands_for_update(cmd: Command) -> Command {
+        Self::augment_subcommands(cmd)
+    }
+
+    fn has_subcommand(name: &str) -> bool {
+        QueryKind::ALL
+            .iter()
+            .any(|kind| subcommand_name(kind) == name)
+    }
+}
+
+/// Queries of the configured directory, loaded before parsing the arguments.
+static CUSTOM_QUERIES: OnceLock<Vec<CustomQuery>> = OnceLock::new();
+
+fn custom_queries() -> &'static [CustomQuery] {
+    CUSTOM_QUERIES.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// One subcommand per custom query, e.g. `pgextras orphaned-uploads`.
+#[derive(Debug)]
+pub struct CustomCommand {
+    pub query: CustomQuery,
+}
+
+fn custom_subcommand_name(query: &CustomQuery) -> String {
+    query.name.replace('_', "-")
+}
+
+impl FromArgMatches for CustomCommand {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let Some((name, _)) = matches.subcommand() else {
+            return Err(clap::Error::new(ErrorKind::MissingSubcommand));
+        };
+        let query = custom_queries()
+            .iter()
+            .find(|query| custom_subcommand_name(query) == name)
+            .ok_or_else(|| clap::Error::new(ErrorKind::InvalidSubcommand))?;
+
+        Ok(Self {
+            query: query.clone(),
+        })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Subcommand for CustomCommand {
+    fn augment_subcommands(cmd: Command) -> Command {
+        custom_queries().iter().fold(cmd, |cmd, query| {
+            cmd.subcommand(Command::new(custom_subcommand_name(query)).about(&query.description))
+        })
+    }
+
+    fn augment_subcommands_for_update(cmd: Command) -> Command {
+        Self::augment_subcommands(cmd)
+    }
+
+    fn has_subcommand(name: &str) -> bool {
+        custom_queries()
+            .iter()
+            .any(|query| custom_subcommand_name(query) == name)
+    }
 }
 
 #[derive(Parser, Debug)]
 pub struct EmptyArgs {}
 
+#[derive(Parser, Debug)]
+pub struct DiagnoseArgs {
+    #[arg(long, help = "Diagnose a snapshot file instead of the database")]
+    pub from: Option<PathBuf>,
+    #[arg(
+        long = "threshold",
+        value_name = "KEY=VALUE",
+        value_parser = parse_threshold,
+        help = "Override a diagnose threshold, e.g. table_cache_hit=0.99. The _critical one must stay past it, e.g. table_cache_hit=0.9 needs table_cache_hit_critical=0.9 or below"
+    )]
+    pub thresholds: Vec<(String, String)>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Exit with 3 when the worst result is a warning, 4 when it is critical"
+    )]
+    pub fail_on: Option<FailOn>,
+    #[arg(long, value_name = "PATH", help = "Also write the report as JUnit XML")]
+    pub junit: Option<PathBuf>,
+    #[arg(long, value_name = "PATH", help = "Also write the report as SARIF")]
+    pub sarif: Option<PathBuf>,
+}
+
+/// Lowest severity making `diagnose` exit with an error code.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum FailOn {
+    Warning,
+    Critical,
+}
+
+/// Exit codes of `diagnose --fail-on`, apart from 1 for errors and 2 for usage errors.
+const EXIT_WARNING: u8 = 3;
+const EXIT_CRITICAL: u8 = 4;
+
+fn parse_threshold(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=VALUE".to_string())?;
+    DiagnoseConfig::default()
+        .set(key, value)
+        .map_err(|e| e.to_string())?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Thresholds of the selected profile, overridden by the environment and `--threshold`.
+fn diagnose_config(
+    config: &Config,
+    args: &PgExtrasArgs,
+    diagnose_args: &DiagnoseArgs,
+) -> Result<DiagnoseConfig, PgExtrasError> {
+    let mut diagnose_config = config
+        .selected_profile(args.profile.as_deref())?
+        .map(|profile| profile.diagnose.clone())
+        .unwrap_or_default();
+    diagnose_config.apply_env()?;
+    for (key, value) in &diagnose_args.thresholds {
+        diagnose_config.set(key, value)?;
+    }
+    diagnose_config.validate()?;
+    Ok(diagnose_config)
+}
+
+#[cfg(feature = "tui")]
+#[derive(Parser, Debug)]
+pub struct TopArgs {
+    #[arg(
+        long,
+        value_parser = humantime::parse_duration,
+        default_value = "2s",
+        help = "Interval between refreshes of the selected view"
+    )]
+    pub refresh: Duration,
+}
+
+#[derive(Parser, Debug)]
+pub struct SignalArgs {
+    #[arg(help = "Backend pids, e.g. from long-running-queries, blocking or locks")]
+    pub pids: Vec<i32>,
+    #[arg(
+        long,
+        help = "Backends blocking others without being blocked themselves"
+    )]
+    pub blocking_roots: bool,
+    #[arg(
+        long,
+        value_parser = humantime::parse_duration,
+        help = "Backends in their current state for longer than this, e.g. 5m"
+    )]
+    pub older_than: Option<Duration>,
+    #[arg(long, help = "Backends in this state, e.g. 'idle in transaction'")]
+    pub state: Option<String>,
+    #[arg(long, help = "Don't ask for confirmation")]
+    pub yes: bool,
+    #[arg(long, help = "Also signal superuser sessions")]
+    pub allow_superuser: bool,
+    #[arg(long, help = "Also signal replication sessions")]
+    pub allow_replication: bool,
+}
+
+impl SignalArgs {
+    fn filter(&self) -> BackendFilter {
+        BackendFilter {
+            pids: self.pids.clone(),
+            blocking_roots: self.blocking_roots,
+            older_than: self.older_than,
+            state: self.state.clone(),
+            allow_superuser: self.allow_superuser,
+            allow_replication: self.allow_replication,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ResetStatsArgs {
+    #[command(subcommand)]
+    pub target: ResetTarget,
+    #[arg(long, global = true, help = "Don't ask for confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ResetTarget {
+    #[command(about = "pg_stat_statements counters, used by calls and outliers")]
+    Statements {
+        #[arg(
+            long,
+            help = "Only the statements of this user, needs pg_stat_statements 1.8"
+        )]
+        username: Option<String>,
+        #[arg(long, help = "Only this query, needs pg_stat_statements 1.8")]
+        queryid: Option<i64>,
+    },
+    #[command(about = "Every table and index counter of the current database")]
+    Database,
+    #[command(about = "Counters of a single table or index")]
+    Table {
+        #[arg(help = "Table or index name, optionally schema-qualified")]
+        name: String,
+    },
+    #[command(about = "Counters shared by the whole cluster")]
+    Shared {
+        #[arg(value_enum)]
+        stats: SharedStats,
+    },
+}
+
+impl ResetTarget {
+    fn stats_reset(&self) -> StatsReset {
+        match self {
+            Self::Statements { username, queryid } => StatsReset::Statements {
+                user: username.clone(),
+                queryid: *queryid,
+            },
+            Self::Database => StatsReset::Database,
+            Self::Table { name } => StatsReset::Table(name.clone()),
+            Self::Shared { stats } => StatsReset::Shared(*stats),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct SnapshotArgs {
+    #[arg(long, help = "File to write the snapshot to")]
+    pub out: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct RenderArgs {
+    #[arg(long, help = "Snapshot file written by the snapshot command")]
+    pub from: PathBuf,
+    #[arg(value_parser = parse_query_kind, help = "Query name, e.g. table-size")]
+    pub query: QueryKind,
+}
+
+#[derive(Parser, Debug)]
+pub struct DiffArgs {
+    #[arg(help = "Snapshot taken before the change")]
+    pub before: PathBuf,
+    #[arg(help = "Snapshot taken after the change")]
+    pub after: PathBuf,
+}
+
+fn parse_query_kind(name: &str) -> Result<QueryKind, String> {
+    QueryKind::from_name(&name.replace('-', "_")).ok_or_else(|| format!("unknown query '{}'", name))
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     match execute().await {
-        Ok(_) => {}
+        Ok(code) => code,
         Err(e) => {
             eprintln!("Error: {}", e);
-            std::process::exit(1);
+            ExitCode::FAILURE
         }
     }
 }
 
 type PG = PgSubcommand;
-async fn execute() -> Result<(), PgExtrasError> {
-    let args = PgExtrasArgs::parse();
-
-    let pool = pg_pool().await?;
-    match args.cmd {
-        PG::AllLocks(_args) => {
-            render_table(all_locks(&pool).await?);
+async fn execute() -> Result<ExitCode, PgExtrasError> {
+    // Custom queries are subcommands, so the config is read before parsing the arguments.
+    // Its errors are returned afterwards, keeping --help usable with a broken config.
+    let config = config_arg().map_or_else(Config::load, Config::from_path);
+    let custom = config
+        .as_ref()
+        .map_err(Clone::clone)
+        .and_then(load_custom_queries);
+    CUSTOM_QUERIES.get_or_init(|| custom.clone().unwrap_or_default());
+    let args = match PgExtrasArgs::try_parse() {
+        Ok(args) => args,
+        // e.g. an unknown subcommand, which may be a query of the broken config
+        Err(e) if e.use_stderr() => {
+            custom?;
+            e.exit()
         }
-        PG::Bloat(_args) => {
-            render_table(bloat(&pool).await?);
-        }
-        PG::Blocking(_args) => {
-            render_table(blocking(None, &pool).await?);
-        }
-        PG::BuffercacheStats(_args) => {
-            render_table(buffercache_stats(&pool).await?);
+        Err(e) => e.exit(),
+    };
+    let config = config?;
+    custom?;
+
+    if args.all_databases
+        && matches!(
+            args.cmd,
+            PG::Snapshot(_)
+                | PG::Render(_)
+                | PG::Diff(_)
+                | PG::Diagnose(DiagnoseArgs { from: Some(_), .. })
+        )
+    {
+        return Err(PgExtrasError::Other(
+            "--all-databases is not supported with snapshots".to_string(),
+        ));
+    }
+
+    if args.all_databases
+        && matches!(&args.cmd, PG::Diagnose(diagnose_args) if diagnose_args.fail_on.is_some()
+            || diagnose_args.junit.is_some()
+            || diagnose_args.sarif.is_some())
+    {
+        return Err(PgExtrasError::Other(
+            "--fail-on, --junit and --sarif are not supported with --all-databases".to_string(),
+        ));
+    }
+    if args.all_databases && matches!(args.cmd, PG::ResetStats(_)) {
+        return Err(PgExtrasError::Other(
+            "--all-databases is not supported by reset-stats".to_string(),
+        ));
+    }
+    if args.all_databases && matches!(args.cmd, PG::Cancel(_) | PG::Terminate(_)) {
+        return Err(PgExtrasError::Other(
+            "--all-databases is not needed, backends of every database are listed".to_string(),
+        ));
+    }
+    if args.watch.is_some() {
+        if !matches!(args.cmd, PG::Query(_)) {
+            return Err(PgExtrasError::Other(
+                "--watch is only supported by built-in queries".to_string(),
+            ));
         }
-        PG::BuffercacheUsage(_args) => {
-            render_table(buffercache_usage(&pool).await?);
+        if args.all_databases || args.interval.is_some() {
+            return Err(PgExtrasError::Other(
+                "--watch is not supported with --all-databases or --interval".to_string(),
+            ));
         }
-        PG::CacheHit(_args) => {
-            render_table(cache_hit(None, &pool).await?);
+    }
+    if args.interval.is_some() && args.all_databases {
+        return Err(PgExtrasError::Other(
+            "--interval is not supported with --all-databases".to_string(),
+        ));
+    }
+    if args.interval.is_some()
+        && !matches!(&args.cmd, PG::Query(command) if RateQuery::from_kind(command.kind).is_some())
+    {
+        let names = RateQuery::ALL
+            .iter()
+            .map(|query| subcommand_name(&query.kind()))
+            .collect::<Vec<_>>();
+        return Err(PgExtrasError::Other(format!(
+            "--interval is only supported by {}",
+            names.join(", ")
+        )));
+    }
+
+    match &args.cmd {
+        PG::Render(render_args) => {
+            let snapshot = Snapshot::from_path(&render_args.from)?;
+            print_output(&render_args.query.from_snapshot(&snapshot)?, args.format)?;
         }
-        PG::Calls(_args) => {
-            render_table(calls(None, &pool).await?);
+        PG::Diff(diff_args) => {
+            let before = Snapshot::from_path(&diff_args.before)?;
+            let diff = before.diff(&Snapshot::from_path(&diff_args.after)?);
+            for (query, reason) in &diff.skipped {
+                eprintln!("Skipping query '{}': {}", query, reason);
+            }
+            print_output(&diff.to_output(), args.format)?;
         }
-        PG::Connections(_args) => {
-            render_table(connections(&pool).await?);
+        PG::Diagnose(
+            diagnose_args @ DiagnoseArgs {
+                from: Some(from), ..
+            },
+        ) => {
+            let diagnose_config = diagnose_config(&config, &args, diagnose_args)?;
+            let checks = Snapshot::from_path(from)?
+                .diagnose(&diagnose_config)
+                .await?;
+            let location = from.display().to_string();
+            return report_diagnose(checks, &location, diagnose_args, args.format);
         }
-        PG::DbSettings(_args) => {
-            render_table(db_settings(&pool).await?);
+        PG::Diagnose(diagnose_args) if args.all_databases => {
+            let client = connect(&config, &args)
+                .await?
+                .with_diagnose_config(diagnose_config(&config, &args, diagnose_args)?);
+            let output = all_databases_output(&client, |client| async move {
+                Ok(diagnose_output(&client.diagnose().await?))
+            })
+            .await?;
+            print_output(&output, args.format)?;
         }
-        PG::Diagnose(_args) => {
-            render_diagnose_report(diagnose(&pool).await?);
+        PG::Diagnose(diagnose_args) => {
+            let diagnose_config = diagnose_config(&config, &args, diagnose_args)?;
+            let client = connect(&config, &args).await?;
+            let checks = run_diagnose(&client, &diagnose_config).await?;
+            let options = client.pool().connect_options();
+            // without a database name, the server connects to the one named after the user
+            let location = options
+                .get_database()
+                .unwrap_or(options.get_username())
+                .to_string();
+            return report_diagnose(checks, &location, diagnose_args, args.format);
         }
-        PG::DuplicateIndexes(_args) => {
-            render_table(duplicate_indexes(&pool).await?);
+        PG::Cancel(signal_args) => {
+            let client = connect(&config, &args).await?;
+            signal(&client, BackendSignal::Cancel, signal_args, args.format).await?;
         }
-        PG::Extensions(_args) => {
-            render_table(extensions(&pool).await?);
+        PG::Terminate(signal_args) => {
+            let client = connect(&config, &args).await?;
+            signal(&client, BackendSignal::Terminate, signal_args, args.format).await?;
         }
-        PG::IndexCacheHit(_args) => {
-            render_table(index_cache_hit(None, &pool).await?);
+        PG::ResetStats(reset_args) => {
+            let client = connect(&config, &args).await?;
+            reset_stats(&client, reset_args).await?;
         }
-        PG::IndexScans(_args) => {
-            render_table(index_scans(None, &pool).await?);
+        PG::Snapshot(snapshot_args) => {
+            let client = connect(&config, &args).await?;
+            let snapshot = Snapshot::capture(&client).await?;
+            snapshot.save(&snapshot_args.out)?;
+            eprintln!(
+                "Captured {} queries into {}, skipped {}",
+                snapshot.queries.len(),
+                snapshot_args.out.display(),
+                snapshot.skipped.len()
+            );
         }
-        PG::IndexSize(_args) => {
-            render_table(index_size(&pool).await?);
+        PG::Query(command) if args.watch.is_some() => {
+            let client = connect(&config, &args).await?;
+            watch(
+                &client,
+                command,
+                args.watch.unwrap_or_default(),
+                args.format,
+            )
+            .await?;
         }
-        PG::IndexUsage(_args) => {
-            render_table(index_usage(None, &pool).await?);
+        PG::Query(command) if args.interval.is_some() => {
+            let interval = args.interval.unwrap_or_default();
+            let client = connect(&config, &args).await?;
+            eprintln!("Sampling for {}...", humantime::format_duration(interval));
+            let rates = client
+                .rates(command.kind, command.params.clone(), interval)
+                .await?;
+            print_output(&rates.output, args.format)?;
         }
-        PG::Indexes(_args) => {
-            render_table(indexes(&pool).await?);
+        PG::Query(command) if args.all_databases => {
+            let client = connect(&config, &args).await?;
+            let output = all_databases_output(&client, |client| {
+                let params = command.params.clone();
+                async move { command.kind.run(&client, params).await }
+            })
+            .await?;
+            print_output(&output, args.format)?;
         }
-        PG::Locks(_args) => {
-            render_table(locks(&pool).await?);
+        PG::Query(command) => {
+            let client = connect(&config, &args).await?;
+            let output = command.kind.run(&client, command.params.clone()).await?;
+            print_output(&output, args.format)?;
         }
-        PG::LongRunningQueries(_args) => {
-            render_table(long_running_queries(&pool).await?);
+        PG::Custom(command) if args.all_databases => {
+            let client = connect(&config, &args).await?;
+            let output =
+                all_databases_output(
+                    &client,
+                    |client| async move { command.query.run(&client).await },
+                )
+                .await?;
+            print_output(&output, args.format)?;
         }
-        PG::NullIndexes(_args) => {
-            render_table(null_indexes(None, &pool).await?);
+        PG::Custom(command) => {
+            let client = connect(&config, &args).await?;
+            print_output(&command.query.run(&client).await?, args.format)?;
         }
-        PG::Outliers(_args) => {
-            render_table(outliers(&pool).await?);
+        #[cfg(feature = "tui")]
+        PG::Top(_) if args.all_databases => {
+            return Err(PgExtrasError::Other(
+                "--all-databases is not supported by top".to_string(),
+            ));
         }
-        PG::Mandelbrot(_args) => {
-            render_table(mandelbrot(&pool).await?);
+        #[cfg(feature = "tui")]
+        PG::Top(top_args) => {
+            let client = connect(&config, &args).await?;
+            pg_extras::tui::run(&client, top_args.refresh).await?;
         }
-        PG::RecordsRank(_args) => {
-            render_table(records_rank(None, &pool).await?);
+        #[cfg(feature = "web")]
+        PG::Web(_) if args.all_databases => {
+            return Err(PgExtrasError::Other(
+                "--all-databases is not supported by the web server".to_string(),
+            ));
         }
-        PG::SeqScans(_args) => {
-            render_table(seq_scans(None, &pool).await?);
+        #[cfg(feature = "web")]
+        PG::Web(_)
+            if args.profile.is_none()
+                && args.connection.is_empty()
+                && !config.profiles.is_empty() =>
+        {
+            start_profiles_web_server(&config, &args).await?;
         }
-        PG::SslUsed(_args) => {
-            render_table(ssl_used(&pool).await?);
+        #[cfg(feature = "web")]
+        PG::Web(_) => {
+            start_web_server(connect(&config, &args).await?).await?;
         }
-        PG::TableCacheHit(_args) => {
-            render_table(table_cache_hit(&pool).await?);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Value of `--config`, needed before the arguments can be parsed.
+fn config_arg() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
         }
-        PG::TableIndexScans(_args) => {
-            render_table(table_index_scans(None, &pool).await?);
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
         }
-        PG::TableIndexesSize(_args) => {
-            render_table(table_indexes_size(None, &pool).await?);
+    }
+    None
+}
+
+/// Custom queries of the config, which must not shadow the built-in subcommands.
+fn load_custom_queries(config: &Config) -> Result<Vec<CustomQuery>, PgExtrasError> {
+    let queries = config.custom_queries()?;
+    let command = PgExtrasArgs::command();
+    if let Some(query) = queries.iter().find(|query| {
+        command
+            .find_subcommand(custom_subcommand_name(query))
+            .is_some()
+    }) {
+        return Err(PgExtrasError::InvalidConfig(format!(
+            "query '{}' has the name of a built-in command",
+            query.name
+        )));
+    }
+    Ok(queries)
+}
+
+fn with_custom_queries(client: PgExtras) -> PgExtras {
+    custom_queries()
+        .iter()
+        .cloned()
+        .fold(client, PgExtras::with_custom_query)
+}
+
+/// Client for the selected profile with the command line options on top.
+async fn connect(config: &Config, args: &PgExtrasArgs) -> Result<PgExtras, PgExtrasError> {
+    let profile = config.selected_profile(args.profile.as_deref())?;
+    let builder = profile
+        .map(|profile| profile.connection())
+        .unwrap_or_default();
+    let pool = args.connection.apply(builder).pool().await?;
+
+    let mut client = PgExtras::new(pool).await?;
+    if let Some(profile) = profile {
+        client = client.with_profile(profile);
+    }
+    if args.statement_timeout.is_some() {
+        let timeouts = Timeouts {
+            statement_timeout: args.statement_timeout,
+            ..*client.timeouts()
+        };
+        client = client.with_timeouts(timeouts);
+    }
+    Ok(with_custom_queries(client))
+}
+
+/// Lists the selected backends and signals them once confirmed, reporting each pid.
+async fn signal(
+    client: &PgExtras,
+    signal: BackendSignal,
+    signal_args: &SignalArgs,
+    format: OutputFormat,
+) -> Result<(), PgExtrasError> {
+    let selected = client.find_backends(&signal_args.filter()).await?;
+    for pid in &selected.missing {
+        eprintln!("No backend with pid {}", pid);
+    }
+    for (backend, protection) in &selected.protected {
+        let hint = match protection {
+            Protection::Superuser => ", use --allow-superuser",
+            Protection::Replication => ", use --allow-replication",
+            Protection::OwnConnection => "",
+        };
+        eprintln!("Skipping pid {}: {}{}", backend.pid, protection, hint);
+    }
+    if selected.targets.is_empty() {
+        eprintln!("No backends to {}", signal.name());
+        return Ok(());
+    }
+
+    print_output(&backends_output(&selected.targets), format)?;
+    let question = format!(
+        "About to {} {} backends, continue?",
+        signal.name(),
+        selected.targets.len()
+    );
+    if !signal_args.yes && !confirm(&question)? {
+        return Err(PgExtrasError::Other("Aborted".to_string()));
+    }
+
+    let mut failed = 0;
+    let results = client
+        .signal_backends(signal, &signal_args.filter(), &selected.targets)
+        .await;
+    for (pid, result) in results {
+        match result {
+            Ok(true) => eprintln!("{}: {}", pid, signal.past_tense()),
+            Ok(false) => eprintln!("{}: gone or changed since it was listed", pid),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", pid, e);
+            }
         }
-        PG::TableSize(_args) => {
-            render_table(table_size(&pool).await?);
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(PgExtrasError::Other(format!(
+            "{} of {} backends could not be {}",
+            failed,
+            selected.targets.len(),
+            signal.past_tense()
+        ))),
+    }
+}
+
+/// Explains what the reset affects and runs it once confirmed.
+async fn reset_stats(client: &PgExtras, reset_args: &ResetStatsArgs) -> Result<(), PgExtrasError> {
+    let reset = reset_args.target.stats_reset();
+    eprintln!("This resets {}.", reset.description());
+    let affected = reset
+        .affected_queries()
+        .iter()
+        .map(subcommand_name)
+        .collect::<Vec<_>>();
+    match affected.is_empty() {
+        true => eprintln!("No pg-extras query reads these counters."),
+        false => eprintln!(
+            "These queries will start over from zero: {}.",
+            affected.join(", ")
+        ),
+    }
+
+    reset.check(client.capabilities())?;
+    if !reset_args.yes && !confirm("Reset the counters?")? {
+        return Err(PgExtrasError::Other("Aborted".to_string()));
+    }
+    client.reset_stats(&reset).await?;
+    eprintln!("Reset {}", reset.description());
+    Ok(())
+}
+
+/// Asks on stderr, only an explicit yes confirms. Without a terminal `--yes` is required.
+fn confirm(question: &str) -> Result<bool, PgExtrasError> {
+    if !io::stdin().is_terminal() {
+        return Err(PgExtrasError::Other(
+            "Confirmation required, pass --yes when not running in a terminal".to_string(),
+        ));
+    }
+
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| PgExtrasError::Other(format!("Cannot read the answer: {}", e)))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Redraws the query until Ctrl-C, which also cancels a query still running on the server.
+/// Formats other than table print every tick one after the other.
+async fn watch(
+    client: &PgExtras,
+    command: &QueryCommand,
+    period: Duration,
+    format: OutputFormat,
+) -> Result<(), PgExtrasError> {
+    let mut watch = Watch::new(client, command.kind, command.params.clone()).await?;
+    let mut ticks = tokio::time::interval(period);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    if format == OutputFormat::Table {
+        print!("\x1b[2J");
+    }
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = ticks.tick() => {}
         }
-        PG::Tables(_args) => {
-            render_table(tables(None, &pool).await?);
+        let tick = tokio::select! {
+            _ = &mut ctrl_c => None,
+            tick = watch.tick() => Some(tick?),
+        };
+        let Some(tick) = tick else {
+            if watch.cancel().await? {
+                eprintln!("Cancelled the running query");
+            }
+            break;
+        };
+
+        match format {
+            OutputFormat::Table => {
+                // Moving the cursor home and clearing below avoids the flicker of a full clear.
+                print!("\x1b[H\x1b[J");
+                tick.table()
+                    .print_tty(false)
+                    .map_err(|e| PgExtrasError::Other(format!("Cannot write output: {}", e)))?;
+            }
+            format => print_output(&tick.output, format)?,
         }
-        PG::TotalIndexSize(_args) => {
-            render_table(total_index_size(&pool).await?);
+    }
+
+    watch.close().await;
+    Ok(())
+}
+
+/// Merges the outputs of every database, databases where `f` fails are skipped.
+async fn all_databases_output<F, Fut>(client: &PgExtras, f: F) -> Result<QueryOutput, PgExtrasError>
+where
+    F: FnMut(PgExtras) -> Fut,
+    Fut: Future<Output = Result<QueryOutput, PgExtrasError>>,
+{
+    let mut outputs = vec![];
+    for (database, result) in client.for_each_database(f).await? {
+        match result {
+            Ok(output) => outputs.push(output.with_database(&database)),
+            Err(e) => eprintln!("Skipping database '{}': {}", database, e),
         }
-        PG::TotalTableSize(_args) => {
-            render_table(total_table_size(&pool).await?);
+    }
+
+    QueryOutput::concat(outputs)
+        .ok_or_else(|| PgExtrasError::Other("The command failed in every database".to_string()))
+}
+
+fn print_output(output: &QueryOutput, format: OutputFormat) -> Result<(), PgExtrasError> {
+    match format {
+        OutputFormat::Table => {
+            render_output(output);
+            Ok(())
         }
-        PG::UnusedIndexes(_args) => {
-            render_table(unused_indexes(None, &pool).await?);
+        format => print_result(write_output(output, format, &mut io::stdout().lock())),
+    }
+}
+
+/// Prints the report, writes the requested report files and picks the `--fail-on` exit code.
+/// SARIF results point at `location`, the database name or the snapshot file.
+fn report_diagnose(
+    checks: Vec<CheckResult>,
+    location: &str,
+    diagnose_args: &DiagnoseArgs,
+    format: OutputFormat,
+) -> Result<ExitCode, PgExtrasError> {
+    if let Some(path) = &diagnose_args.junit {
+        write_report_file(path, |file| write_junit_report(&checks, file))?;
+    }
+    if let Some(path) = &diagnose_args.sarif {
+        write_report_file(path, |file| write_sarif_report(&checks, location, file))?;
+    }
+
+    let worst = checks.iter().map(|check| check.severity).max();
+    print_diagnose(checks, format)?;
+    Ok(ExitCode::from(fail_on_exit_code(
+        worst,
+        diagnose_args.fail_on,
+    )))
+}
+
+fn fail_on_exit_code(worst: Option<Severity>, fail_on: Option<FailOn>) -> u8 {
+    let fail_on = match fail_on {
+        Some(FailOn::Warning) => Severity::Warning,
+        Some(FailOn::Critical) => Severity::Critical,
+        None => return 0,
+    };
+    match worst {
+        Some(Severity::Critical) => EXIT_CRITICAL,
+        Some(worst) if worst >= fail_on => EXIT_WARNING,
+        _ => 0,
+    }
+}
+
+fn write_report_file(
+    path: &Path,
+    write: impl FnOnce(&mut io::BufWriter<File>) -> io::Result<()>,
+) -> Result<(), PgExtrasError> {
+    let mut file = io::BufWriter::new(File::create(path).map_err(|e| write_error(path, e))?);
+    write(&mut file)
+        .and_then(|_| file.flush())
+        .map_err(|e| write_error(path, e))
+}
+
+fn write_error(path: &Path, error: io::Error) -> PgExtrasError {
+    PgExtrasError::Other(format!("Cannot write {}: {}", path.display(), error))
+}
+
+fn print_diagnose(checks: Vec<CheckResult>, format: OutputFormat) -> Result<(), PgExtrasError> {
+    match format {
+        OutputFormat::Table => {
+            render_diagnose_report(checks);
+            Ok(())
         }
-        PG::VacuumStats(_args) => {
-            render_table(vacuum_stats(&pool).await?);
+        format => print_result(write_diagnose_report(
+            &checks,
+            format,
+            &mut io::stdout().lock(),
+        )),
+    }
+}
+
+/// Ignores a closed stdout, e.g. when piping into `head`.
+fn print_result(result: io::Result<()>) -> Result<(), PgExtrasError> {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(PgExtrasError::Other(format!("Cannot write output: {}", e)))
         }
-        #[cfg(feature = "web")]
-        PG::Web(_args) => {
-            start_web_server(pool).await?;
+        _ => Ok(()),
+    }
+}
+
+/// Serves every profile of the config, profiles that cannot connect are skipped.
+#[cfg(feature = "web")]
+async fn start_profiles_web_server(
+    config: &Config,
+    args: &PgExtrasArgs,
+) -> Result<(), PgExtrasError> {
+    let mut connections = tokio::task::JoinSet::new();
+    for (name, profile) in config.profiles.clone() {
+        let statement_timeout = args.statement_timeout;
+        connections.spawn(async move {
+            let client = profile
+                .client()
+                .await
+                .map(|client| match statement_timeout {
+                    Some(_) => client.with_timeouts(Timeouts {
+                        statement_timeout,
+                        ..Default::default()
+                    }),
+                    None => client,
+                })
+                .map(with_custom_queries);
+            (name, client)
+        });
+    }
+
+    let mut clients = std::collections::BTreeMap::new();
+    while let Some(connection) = connections.join_next().await {
+        match connection.map_err(|e| PgExtrasError::Other(e.to_string()))? {
+            (name, Ok(client)) => {
+                clients.insert(name, client);
+            }
+            (name, Err(e)) => eprintln!("Skipping profile '{}': {}", name, e),
         }
     }
 
-    Ok(())
+    let default_profile = config
+        .default_profile
+        .clone()
+        .filter(|name| clients.contains_key(name))
+        .or_else(|| clients.keys().next().cloned())
+        .ok_or_else(|| PgExtrasError::Other("No profile could connect".to_string()))?;
+
+    let app = pg_extras::web::routes::app_with_profiles(clients, default_profile).await;
+    serve(app).await
+}
+
+#[cfg(feature = "web")]
+async fn start_web_server(client: PgExtras) -> Result<(), PgExtrasError> {
+    serve(pg_extras::web::routes::app(client).await).await
 }
 
 #[cfg(feature = "web")]
-async fn start_web_server(pool: PgPool) -> Result<(), PgExtrasError> {
+async fn serve(app: axum::Router) -> Result<(), PgExtrasError> {
     let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
 
     if tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
@@ -253,7 +1128,6 @@ async fn start_web_server(pool: PgPool) -> Result<(), PgExtrasError> {
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
         .await
         .unwrap();
-    let app = pg_extras::web::routes::app(pool).await;
 
     println!(
         "Available on http://{}/pg_extras",
@@ -262,3 +1136,51 @@ async fn start_web_server(pool: PgPool) -> Result<(), PgExtrasError> {
     axum::serve(listener, app).await.unwrap();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        PgExtrasArgs::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_query_params() {
+        let args =
+            PgExtrasArgs::try_parse_from(["pgextras", "null-indexes", "--min-size", "0"]).unwrap();
+        let PG::Query(command) = args.cmd else {
+            panic!("expected a query subcommand");
+        };
+        assert_eq!(command.kind, QueryKind::NullIndexes);
+        assert_eq!(
+            command.params.get(&QueryParam::MinRelationSizeMb),
+            Some(&"0".to_string())
+        );
+
+        assert!(PgExtrasArgs::try_parse_from(["pgextras", "calls", "--limit", "-1"]).is_err());
+        assert!(PgExtrasArgs::try_parse_from(["pgextras", "bloat", "--limit", "5"]).is_err());
+    }
+
+    #[test]
+    fn picks_fail_on_exit_codes() {
+        assert_eq!(fail_on_exit_code(Some(Severity::Critical), None), 0);
+        assert_eq!(
+            fail_on_exit_code(Some(Severity::Warning), Some(FailOn::Warning)),
+            EXIT_WARNING
+        );
+        assert_eq!(
+            fail_on_exit_code(Some(Severity::Critical), Some(FailOn::Warning)),
+            EXIT_CRITICAL
+        );
+        assert_eq!(
+            fail_on_exit_code(Some(Severity::Warning), Some(FailOn::Critical)),
+            0
+        );
+        assert_eq!(
+            fail_on_exit_code(Some(Severity::Info), Some(FailOn::Warning)),
+            0
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use crate::queries::shared::{duration_secs, format_duration, get_duration};
+use crate::{fetch_with_timeouts, query_error, PgExtras, PgExtrasError, QueryOutput};
+use serde_json::json;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::PgRow;
+use sqlx::{Postgres, Row};
+use std::fmt;
+use std::time::Duration;
+
+/// How to stop a backend: `Cancel` stops its current query, `Terminate` closes its connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendSignal {
+    Cancel,
+    Terminate,
+}
+
+/// Why a backend matching a filter is not signalled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    OwnConnection,
+    Superuser,
+    Replication,
+}
+
+/// Selects the backends to signal. Criteria are combined, at least one is required.
+#[derive(Debug, Clone, Default)]
+pub struct BackendFilter {
+    pub pids: Vec<i32>,
+    /// Backends blocking others without being blocked themselves.
+    pub blocking_roots: bool,
+    /// Minimum time spent in the current state, or running the current query when active.
+    pub older_than: Option<Duration>,
+    /// Value of `pg_stat_activity.state`, e.g. `idle in transaction`.
+    pub state: Option<String>,
+    pub allow_superuser: bool,
+    pub allow_replication: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Backend {
+    pub pid: i32,
+    pub usename: String,
+    pub application_name: String,
+    pub state: String,
+    pub backend_type: String,
+    /// Microseconds since the epoch, telling apart a backend reusing the pid.
+    pub backend_start: i64,
+    #[serde(with = "duration_secs")]
+    pub duration: Duration,
+    pub query: String,
+    /// A connection of the client, the one used to look up the backends or one sharing
+    /// the `application_name` of its pool.
+    pub own: bool,
+    pub superuser: bool,
+    pub replication: bool,
+    pub blocking_root: bool,
+}
+
+/// Backends matching a `BackendFilter`.
+#[derive(Debug, Clone, Default)]
+pub struct BackendTargets {
+    pub targets: Vec<Backend>,
+    /// Matching backends which are not signalled, with the reason.
+    pub protected: Vec<(Backend, Protection)>,
+    /// Requested pids without a backend.
+    pub missing: Vec<i32>,
+}
+
+impl BackendSignal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cancel => "cancel",
+            Self::Terminate => "terminate",
+        }
+    }
+
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            Self::Cancel => "cancelled",
+            Self::Terminate => "terminated",
+        }
+    }
+
+    fn function(&self) -> &'static str {
+        match self {
+            Self::Cancel => "pg_cancel_backend",
+            Self::Terminate => "pg_terminate_backend",
+        }
+    }
+}
+
+impl BackendFilter {
+    pub fn is_empty(&self) -> bool {
+        self.pids.is_empty()
+            && !self.blocking_roots
+            && self.older_than.is_none()
+            && self.state.is_none()
+    }
+
+    fn matches(&self, backend: &Backend) -> bool {
+        (self.pids.is_empty() || self.pids.contains(&backend.pid))
+            && (!self.blocking_roots || backend.blocking_root)
+            && self
+                .older_than
+                .is_none_or(|older_than| backend.duration >= older_than)
+            && self
+                .state
+                .as_ref()
+                .is_none_or(|state| backend.state == *state)
+    }
+
+    /// Why a matching backend must not be signalled, if it must not.
+    fn protection(&self, backend: &Backend) -> Option<Protection> {
+        if backend.own {
+            Some(Protection::OwnConnection)
+        } else if backend.superuser && !self.allow_superuser {
+            Some(Protection::Superuser)
+        } else if backend.replication && !self.allow_replication {
+            Some(Protection::Replication)
+        } else {
+            None
+        }
+    }
+
+    /// Splits the backends into targets and protected ones.
+    fn select(&self, backends: Vec<Backend>) -> BackendTargets {
+        let mut selected = BackendTargets {
+            missing: self
+                .pids
+                .iter()
+                .filter(|pid| !backends.iter().any(|backend| backend.pid == **pid))
+                .copied()
+                .collect(),
+            ..Default::default()
+        };
+        for backend in backends.into_iter().filter(|backend| self.matches(backend)) {
+            match self.protection(&backend) {
+                Some(reason) => selected.protected.push((backend, reason)),
+                None => selected.targets.push(backend),
+            }
+        }
+        selected
+    }
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::OwnConnection => "own connection",
+            Self::Superuser => "superuser session",
+            Self::Replication => "replication session",
+        })
+    }
+}
+
+impl Backend {
+    fn new(row: &PgRow) -> Self {
+        Self {
+            pid: row.try_get("pid").unwrap_or_default(),
+            usename: row.try_get("usename").unwrap_or_default(),
+            application_name: row.try_get("application_name").unwrap_or_default(),
+            state: row.try_get("state").unwrap_or_default(),
+            backend_type: row.try_get("backend_type").unwrap_or_default(),
+            backend_start: row.try_get("backend_start").unwrap_or_default(),
+            duration: get_duration(row, "duration"),
+            query: row.try_get("query").unwrap_or_default(),
+            own: row.try_get("own").unwrap_or_default(),
+            superuser: row.try_get("superuser").unwrap_or_default(),
+            replication: row.try_get("replication").unwrap_or_default(),
+            blocking_root: row.try_get("blocking_root").unwrap_or_default(),
+        }
+    }
+}
+
+/// Table of the backends, for `write_output`.
+pub fn backends_output(backends: &[Backend]) -> QueryOutput {
+    let headers = [
+        "pid",
+        "usename",
+        "application_name",
+        "state",
+        "duration",
+        "query",
+    ];
+    QueryOutput {
+        description: "Backends to signal".to_string(),
+        headers: headers.iter().map(|header| header.to_string()).collect(),
+        rows: backends
+            .iter()
+            .map(|backend| {
+                json!({
+                    "pid": backend.pid,
+                    "usename": backend.usename,
+                    "application_name": backend.application_name,
+                    "state": backend.state,
+                    "duration": backend.duration.as_secs_f64(),
+                    "query": backend.query,
+                })
+            })
+            .collect(),
+        cells: backends
+            .iter()
+            .map(|backend| {
+                vec![
+                    backend.pid.to_string(),
+                    backend.usename.clone(),
+                    backend.application_name.clone(),
+                    backend.state.clone(),
+                    format_duration(&backend.duration),
+                    backend.query.clone(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+impl PgExtras {
+    /// Backends matching the filter. Own, superuser and replication backends are protected
+    /// unless allowed by the filter.
+    pub async fn find_backends(
+        &self,
+        filter: &BackendFilter,
+    ) -> Result<BackendTargets, PgExtrasError> {
+        if filter.is_empty() {
+            return Err(PgExtrasError::Other(
+                "No backends selected, pass pids or a filter".to_string(),
+            ));
+        }
+
+        let timeouts = self.query_timeouts("backends");
+        let query = sqlx::query(include_str!("sql/backends.sql")).bind(self.application_name());
+        let backends = fetch_with_timeouts(&mut *self.acquire().await?, query, &timeouts)
+            .await
+            .map_err(|e| query_error("backends", &[], e, self.capabilities(), &timeouts))?
+            .iter()
+            .map(Backend::new)
+            .collect();
+        Ok(filter.select(backends))
+    }
+
+    /// Signals each backend listed by `find_backends`, returning whether it was signalled.
+    /// The protections are checked again when signalling, so `false` means the backend is
+    /// gone, its pid belongs to a new backend or it is protected by `filter` now.
+    /// Backends are signalled one by one, so a failure doesn't stop the others.
+    pub async fn signal_backends(
+        &self,
+        signal: BackendSignal,
+        filter: &BackendFilter,
+        backends: &[Backend],
+    ) -> Vec<(i32, Result<bool, PgExtrasError>)> {
+        let mut results = vec![];
+        for backend in backends {
+            results.push((
+                backend.pid,
+                self.signal_backend(signal, filter, backend).await,
+            ));
+        }
+        results
+    }
+
+    async fn signal_backend(
+        &self,
+        signal: BackendSignal,
+        filter: &BackendFilter,
+        backend: &Backend,
+    ) -> Result<bool, PgExtrasError> {
+        let timeouts = self.query_timeouts(signal.function());
+        let query = sqlx::query(include_str!("sql/signal_backend.sql"))
+            .bind(backend.pid)
+            .bind(backend.backend_start)
+            .bind(filter.allow_superuser)
+            .bind(filter.allow_replication)
+            .bind(signal == BackendSignal::Terminate)
+            .bind(self.application_name());
+        let rows = fetch_with_timeouts(&mut *self.acquire().await?, query, &timeouts)
+            .await
+            .map_err(|e| query_error(signal.function(), &[], e, self.capabilities(), &timeouts))?;
+        Ok(rows
+            .first()
+            .and_then(|row| row.try_get(0).ok())
+            .unwrap_or(false))
+    }
+
+    /// `application_name` of the pool connections, which are never signalled.
+    fn application_name(&self) -> Option<String> {
+        self.pool()
+            .connect_options()
+            .get_application_name()
+            .map(|name| name.to_string())
+    }
+
+    async fn acquire(&self) -> Result<PoolConnection<Postgres>, PgExtrasError> {
+        self.pool()
+            .acquire()
+            .await
+            .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(pid: i32, state: &str, secs: u64) -> Backend {
+        Backend {
+            pid,
+            usename: "app".to_string(),
+            application_name: String::new(),
+            state: state.to_string(),
+            backend_type: "client backend".to_string(),
+            backend_start: 0,
+            duration: Duration::from_secs(secs),
+            query: String::new(),
+            own: false,
+            superuser: false,
+            replication: false,
+            blocking_root: false,
+        }
+    }
+
+    fn pids(backends: &[Backend]) -> Vec<i32> {
+        backends.iter().map(|backend| backend.pid).collect()
+    }
+
+    #[test]
+    fn combines_filter_criteria() {
+        let backends = vec![
+            backend(1, "idle in transaction", 600),
+            backend(2, "idle in transaction", 10),
+            backend(3, "active", 600),
+        ];
+        let filter = BackendFilter {
+            older_than: Some(Duration::from_secs(300)),
+            state: Some("idle in transaction".to_string()),
+            ..Default::default()
+        };
+
+        let selected = filter.select(backends);
+        assert_eq!(pids(&selected.targets), vec![1]);
+        assert!(selected.protected.is_empty());
+    }
+
+    #[test]
+    fn protects_own_superuser_and_replication_backends() {
+        let mut own = backend(1, "active", 0);
+        own.own = true;
+        let mut superuser = backend(2, "active", 0);
+        superuser.superuser = true;
+        let mut walsender = backend(3, "active", 0);
+        walsender.replication = true;
+
+        let mut filter = BackendFilter {
+            pids: vec![1, 2, 3, 4],
+            ..Default::default()
+        };
+        let backends = vec![own, superuser, walsender];
+        let selected = filter.select(backends.clone());
+        assert!(selected.targets.is_empty());
+        assert_eq!(
+            selected
+                .protected
+                .iter()
+                .map(|(_, reason)| *reason)
+                .collect::<Vec<_>>(),
+            vec![
+                Protection::OwnConnection,
+                Protection::Superuser,
+                Protection::Replication
+            ]
+        );
+        assert_eq!(selected.missing, vec![4]);
+
+        filter.allow_superuser = true;
+        filter.allow_replication = true;
+        assert_eq!(pids(&filter.select(backends).targets), vec![2, 3]);
+    }
+}
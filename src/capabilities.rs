@@ -0,0 +1,136 @@
+use crate::{PgExtrasError, PgStatsVersion, NEW_PG_STAT_STATEMENTS, PG_STAT_STATEMENTS_17};
+use semver::Version;
+use sqlx::{Executor, Postgres, Row};
+use std::collections::HashMap;
+
+const PROBE_QUERY: &str = "
+SELECT
+  current_setting('server_version_num')::integer AS server_version_num,
+  pg_is_in_recovery() AS in_recovery,
+  r.rolsuper AS superuser,
+  pg_has_role(current_user, 'pg_read_all_stats', 'USAGE') AS read_all_stats,
+  pg_has_role(current_user, 'pg_signal_backend', 'USAGE') AS signal_backend,
+  ARRAY(SELECT extname::text FROM pg_extension ORDER BY extname) AS extension_names,
+  ARRAY(SELECT extversion FROM pg_extension ORDER BY extname) AS extension_versions
+FROM pg_roles r
+WHERE r.rolname = current_user";
+
+/// Privileges of the connected role that affect what the queries can see.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RolePrivileges {
+    pub superuser: bool,
+    /// Member of `pg_read_all_stats`, required to see other users' queries.
+    pub read_all_stats: bool,
+    /// Member of `pg_signal_backend`, required to cancel other users' queries.
+    pub signal_backend: bool,
+}
+
+/// Server features detected once per client and reused by every query.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ServerCapabilities {
+    pub server_version_num: i32,
+    /// Installed extensions mapped to their installed versions.
+    pub extensions: HashMap<String, String>,
+    pub in_recovery: bool,
+    pub privileges: RolePrivileges,
+}
+
+impl ServerCapabilities {
+    /// Probes the server in a single round trip.
+    pub async fn detect<'e, E>(executor: E) -> Result<Self, PgExtrasError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let row = sqlx::query(PROBE_QUERY)
+            .fetch_one(executor)
+            .await
+            .map_err(|e| PgExtrasError::query_failed("server_capabilities", e))?;
+
+        let names: Vec<String> = row.try_get("extension_names").unwrap_or_default();
+        let versions: Vec<String> = row.try_get("extension_versions").unwrap_or_default();
+
+        Ok(Self {
+            server_version_num: row.try_get("server_version_num").unwrap_or_default(),
+            extensions: names.into_iter().zip(versions).collect(),
+            in_recovery: row.try_get("in_recovery").unwrap_or_default(),
+            privileges: RolePrivileges {
+                superuser: row.try_get("superuser").unwrap_or_default(),
+                read_all_stats: row.try_get("read_all_stats").unwrap_or_default(),
+                signal_backend: row.try_get("signal_backend").unwrap_or_default(),
+            },
+        })
+    }
+
+    /// Major version of the server, e.g. `16` for `160004`.
+    pub fn server_major_version(&self) -> i32 {
+        self.server_version_num / 10_000
+    }
+
+    pub fn extension_version(&self, name: &str) -> Option<&str> {
+        self.extensions.get(name).map(|v| v.as_str())
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains_key(name)
+    }
+
+    /// Variant of the pg_stat_statements views, `Standard` if the extension is missing.
+    pub fn pg_stats_version(&self) -> PgStatsVersion {
+        let default_version = NEW_PG_STAT_STATEMENTS.clone();
+        let version = self
+            .extension_version("pg_stat_statements")
+            .and_then(|v| Version::parse(&format!("{}.0", v)).ok())
+            .unwrap_or(default_version.clone());
+
+        if version < default_version {
+            PgStatsVersion::Legacy
+        } else if version >= *PG_STAT_STATEMENTS_17 {
+            PgStatsVersion::Pg17
+        } else {
+            PgStatsVersion::Standard
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_pg_stat_statements(version: &str) -> ServerCapabilities {
+        ServerCapabilities {
+            extensions: [("pg_stat_statements".to_string(), version.to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pg_stats_version() {
+        assert_eq!(
+            with_pg_stat_statements("1.7").pg_stats_version(),
+            PgStatsVersion::Legacy
+        );
+        assert_eq!(
+            with_pg_stat_statements("1.10").pg_stats_version(),
+            PgStatsVersion::Standard
+        );
+        assert_eq!(
+            with_pg_stat_statements("1.11").pg_stats_version(),
+            PgStatsVersion::Pg17
+        );
+        assert_eq!(
+            ServerCapabilities::default().pg_stats_version(),
+            PgStatsVersion::Standard
+        );
+    }
+
+    #[test]
+    fn server_major_version() {
+        let capabilities = ServerCapabilities {
+            server_version_num: 170002,
+            ..Default::default()
+        };
+        assert_eq!(capabilities.server_major_version(), 17);
+    }
+}
@@ -0,0 +1,530 @@
+use crate::config::Profile;
+use crate::custom_queries::CustomQuery;
+use crate::diagnose::config::DiagnoseConfig;
+use crate::diagnose::run::{run_diagnose, CheckRegistry, CheckResult, DiagnoseCheck};
+use crate::{
+    get_default_schema, get_rows, limit_params, param_args, pg_pool, schema_params, AllLocks,
+    Bloat, Blocking, BuffercacheStats, BuffercacheUsage, CacheHit, Calls, Connections, DbSettings,
+    DuplicateIndexes, Extensions, IndexCacheHit, IndexScans, IndexSize, IndexUsage, Indexes, Locks,
+    LongRunningQueries, Mandelbrot, NullIndexes, Outliers, PgExtrasError, Query, QueryParam,
+    RecordsRank, SeqScans, ServerCapabilities, SslUsed, TableCacheHit, TableIndexScans,
+    TableIndexesSize, TableSize, Tables, TotalIndexSize, TotalTableSize, UnusedIndexes,
+    VacuumStats,
+};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DATABASES_QUERY: &str = "
+SELECT datname::text
+FROM pg_database
+WHERE NOT datistemplate AND datallowconn
+ORDER BY datname";
+
+/// Limits applied with `SET LOCAL` around each query. `None` keeps the server setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    pub statement_timeout: Option<Duration>,
+    pub lock_timeout: Option<Duration>,
+}
+
+impl Timeouts {
+    pub fn is_empty(&self) -> bool {
+        self.statement_timeout.is_none() && self.lock_timeout.is_none()
+    }
+
+    /// Values set in `self`, falling back to `defaults` for the rest.
+    pub fn or(self, defaults: Timeouts) -> Timeouts {
+        Timeouts {
+            statement_timeout: self.statement_timeout.or(defaults.statement_timeout),
+            lock_timeout: self.lock_timeout.or(defaults.lock_timeout),
+        }
+    }
+}
+
+/// Database client that probes server capabilities once and reuses them for every query.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+/// use pg_extras::{render_table, PgExtras};
+///
+/// let client = PgExtras::connect().await?;
+/// render_table(client.cache_hit(None).await?);
+/// render_table(client.calls(None).await?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PgExtras {
+    pool: Pool<Postgres>,
+    capabilities: Arc<ServerCapabilities>,
+    timeouts: Timeouts,
+    query_timeouts: HashMap<String, Timeouts>,
+    default_schema: Option<String>,
+    diagnose_config: DiagnoseConfig,
+    /// Whether `diagnose` applies the `PG_EXTRAS_DIAGNOSE_<KEY>` overrides, unset by
+    /// `with_diagnose_config`.
+    diagnose_env: bool,
+    checks: CheckRegistry,
+    custom_queries: Vec<CustomQuery>,
+}
+
+impl PgExtras {
+    /// Wraps an existing pool, detecting the server capabilities.
+    pub async fn new(pool: Pool<Postgres>) -> Result<Self, PgExtrasError> {
+        let capabilities = ServerCapabilities::detect(&pool).await?;
+        Ok(Self {
+            pool,
+            capabilities: Arc::new(capabilities),
+            timeouts: Timeouts::default(),
+            query_timeouts: HashMap::new(),
+            default_schema: None,
+            diagnose_config: DiagnoseConfig::default(),
+            diagnose_env: true,
+            checks: CheckRegistry::default(),
+            custom_queries: Vec::new(),
+        })
+    }
+
+    /// Connects using the same configuration as `pg_pool`.
+    pub async fn connect() -> Result<Self, PgExtrasError> {
+        Self::new(pg_pool().await?).await
+    }
+
+    pub fn pool(&self) -> &Pool<Postgres> {
+        &self.pool
+    }
+
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    /// Applies the timeouts to every query run by this client.
+    ///
+    /// ```no_run
+    /// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+    /// use pg_extras::{PgExtras, Timeouts};
+    /// use std::time::Duration;
+    ///
+    /// let client = PgExtras::connect().await?.with_timeouts(Timeouts {
+    ///     statement_timeout: Some(Duration::from_secs(5)),
+    ///     lock_timeout: Some(Duration::from_secs(1)),
+    /// });
+    /// let bloat = client
+    ///     .with_query_timeouts("bloat", Timeouts {
+    ///         statement_timeout: Some(Duration::from_secs(60)),
+    ///         ..Default::default()
+    ///     })
+    ///     .bloat()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Overrides the client timeouts for a single query, e.g. `"bloat"`.
+    /// Unset values fall back to the client timeouts.
+    pub fn with_query_timeouts(mut self, query: &str, timeouts: Timeouts) -> Self {
+        self.query_timeouts.insert(query.to_string(), timeouts);
+        self
+    }
+
+    pub fn timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+
+    /// Timeouts used for the query with the given name.
+    pub fn query_timeouts(&self, query: &str) -> Timeouts {
+        match self.query_timeouts.get(query) {
+            Some(timeouts) => timeouts.or(self.timeouts),
+            None => self.timeouts,
+        }
+    }
+
+    /// Schema used by queries called without one, instead of `PG_EXTRAS_SCHEMA`.
+    pub fn with_default_schema(mut self, schema: impl Into<String>) -> Self {
+        self.default_schema = Some(schema.into());
+        self
+    }
+
+    /// Schema used by queries called without one.
+    pub fn default_schema(&self) -> String {
+        self.default_schema
+            .clone()
+            .unwrap_or_else(get_default_schema)
+    }
+
+    /// Replaces the thresholds used by `diagnose`, which otherwise applies the
+    /// `PG_EXTRAS_DIAGNOSE_<KEY>` overrides to the defaults. The overrides are not applied
+    /// to the new ones.
+    pub fn with_diagnose_config(mut self, diagnose_config: DiagnoseConfig) -> Self {
+        self.diagnose_config = diagnose_config;
+        self.diagnose_env = false;
+        self
+    }
+
+    pub fn diagnose_config(&self) -> &DiagnoseConfig {
+        &self.diagnose_config
+    }
+
+    /// Adds a check to `diagnose`, run after the built-in ones. Snapshots only run the
+    /// built-in checks.
+    pub fn with_check(mut self, check: impl DiagnoseCheck + 'static) -> Self {
+        self.checks.register(check);
+        self
+    }
+
+    pub fn checks(&self) -> &CheckRegistry {
+        &self.checks
+    }
+
+    /// Adds a query read from a `.sql` file, also run by `diagnose` when it has a check.
+    pub fn with_custom_query(mut self, query: CustomQuery) -> Self {
+        if query.check.is_some() {
+            self.checks.register(query.clone());
+        }
+        self.custom_queries.push(query);
+        self
+    }
+
+    pub fn custom_queries(&self) -> &[CustomQuery] {
+        &self.custom_queries
+    }
+
+    pub fn custom_query(&self, name: &str) -> Option<&CustomQuery> {
+        self.custom_queries.iter().find(|query| query.name == name)
+    }
+
+    /// Applies the schema, statement timeout and diagnose thresholds of a config profile.
+    /// `diagnose` still applies the `PG_EXTRAS_DIAGNOSE_<KEY>` overrides on top of them.
+    pub fn with_profile(mut self, profile: &Profile) -> Self {
+        if let Some(schema) = &profile.schema {
+            self = self.with_default_schema(schema);
+        }
+        if profile.statement_timeout.is_some() {
+            self.timeouts.statement_timeout = profile.statement_timeout;
+        }
+        self.diagnose_config = profile.diagnose.clone();
+        self
+    }
+
+    /// Probes the server again, e.g. after installing an extension.
+    pub async fn refresh_capabilities(&mut self) -> Result<(), PgExtrasError> {
+        self.capabilities = Arc::new(ServerCapabilities::detect(&self.pool).await?);
+        Ok(())
+    }
+
+    /// Databases of the cluster that accept connections, excluding templates.
+    pub async fn databases(&self) -> Result<Vec<String>, PgExtrasError> {
+        sqlx::query_scalar(DATABASES_QUERY)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| PgExtrasError::query_failed("databases", e))
+    }
+
+    /// Connects to another database of the same cluster with a single connection pool,
+    /// keeping the timeouts, default schema and diagnose thresholds of this client.
+    pub async fn for_database(&self, dbname: &str) -> Result<Self, PgExtrasError> {
+        let options = (*self.pool.connect_options()).clone().database(dbname);
+        // extensions are installed per database
+        let mut client = self.with_single_connection(options).await?;
+        client.refresh_capabilities().await?;
+        Ok(client)
+    }
+
+    /// Client with the settings and capabilities of this one, using a single connection pool.
+    pub(crate) async fn with_single_connection(
+        &self,
+        options: PgConnectOptions,
+    ) -> Result<Self, PgExtrasError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(self.pool.options().get_acquire_timeout())
+            .connect_with(options)
+            .await
+            .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))?;
+
+        Ok(Self {
+            pool,
+            capabilities: self.capabilities.clone(),
+            timeouts: self.timeouts,
+            query_timeouts: self.query_timeouts.clone(),
+            default_schema: self.default_schema.clone(),
+            diagnose_config: self.diagnose_config.clone(),
+            diagnose_env: self.diagnose_env,
+            checks: self.checks.clone(),
+            custom_queries: self.custom_queries.clone(),
+        })
+    }
+
+    /// Calls `f` with a short-lived client for each of the `databases`, one at a time.
+    /// Results are returned per database, so a failure in one doesn't stop the others.
+    ///
+    /// ```no_run
+    /// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+    /// use pg_extras::PgExtras;
+    ///
+    /// let client = PgExtras::connect().await?;
+    /// let results = client
+    ///     .for_each_database(|client| async move { client.unused_indexes(None).await })
+    ///     .await?;
+    /// for (database, indexes) in results {
+    ///     println!("{}: {:?}", database, indexes);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn for_each_database<T, F, Fut>(
+        &self,
+        mut f: F,
+    ) -> Result<Vec<(String, Result<T, PgExtrasError>)>, PgExtrasError>
+    where
+        F: FnMut(PgExtras) -> Fut,
+        Fut: Future<Output = Result<T, PgExtrasError>>,
+    {
+        let mut results = vec![];
+        for database in self.databases().await? {
+            let result = match self.for_database(&database).await {
+                Ok(client) => {
+                    let pool = client.pool.clone();
+                    let result = f(client).await;
+                    pool.close().await;
+                    result
+                }
+                Err(e) => Err(e),
+            };
+            results.push((database, result));
+        }
+        Ok(results)
+    }
+
+    /// Returns table and index bloat in your database ordered by most wasteful.
+    pub async fn bloat(&self) -> Result<Vec<Bloat>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Lists queries that are blocking other queries.
+    pub async fn blocking(&self, limit: Option<String>) -> Result<Vec<Blocking>, PgExtrasError> {
+        self.get_rows(Some(limit_params(limit))).await
+    }
+
+    /// Returns statistics about query calls in the database.
+    pub async fn calls(&self, limit: Option<String>) -> Result<Vec<Calls>, PgExtrasError> {
+        self.get_rows(Some(limit_params(limit))).await
+    }
+
+    /// Lists all installed PostgreSQL extensions.
+    pub async fn extensions(&self) -> Result<Vec<Extensions>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows cache hit rates for tables.
+    pub async fn table_cache_hit(&self) -> Result<Vec<TableCacheHit>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Lists all tables in the database with their basic information.
+    pub async fn tables(&self, schema: Option<String>) -> Result<Vec<Tables>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows index cache hit rates.
+    pub async fn index_cache_hit(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<IndexCacheHit>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Lists all indexes in the database.
+    pub async fn indexes(&self) -> Result<Vec<Indexes>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows the size of all indexes, ordered by size.
+    pub async fn index_size(&self) -> Result<Vec<IndexSize>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows statistics about index usage.
+    pub async fn index_usage(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<IndexUsage>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows statistics about index scans.
+    pub async fn index_scans(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<IndexScans>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows indexes that contain mostly NULL values.
+    pub async fn null_indexes(
+        &self,
+        min_relation_size_mb: Option<String>,
+    ) -> Result<Vec<NullIndexes>, PgExtrasError> {
+        self.get_rows(Some(param_args(
+            QueryParam::MinRelationSizeMb,
+            min_relation_size_mb,
+        )))
+        .await
+    }
+
+    /// Shows information about locks in the database.
+    pub async fn locks(&self) -> Result<Vec<Locks>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows detailed information about all locks in the database.
+    pub async fn all_locks(&self) -> Result<Vec<AllLocks>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Lists currently running queries that have been running for a long time.
+    pub async fn long_running_queries(&self) -> Result<Vec<LongRunningQueries>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Generates a Mandelbrot set as a test query.
+    pub async fn mandelbrot(&self) -> Result<Vec<Mandelbrot>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows queries with the longest execution time in aggregate.
+    pub async fn outliers(&self) -> Result<Vec<Outliers>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows estimated number of rows in each table, ordered by estimated count.
+    pub async fn records_rank(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<RecordsRank>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows statistics about sequential scans performed on tables.
+    pub async fn seq_scans(&self, schema: Option<String>) -> Result<Vec<SeqScans>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows statistics about index scans performed on tables.
+    pub async fn table_index_scans(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<TableIndexScans>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows total size of all indexes for each table.
+    pub async fn table_indexes_size(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<TableIndexesSize>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows disk space used by each table, excluding indexes.
+    pub async fn table_size(&self) -> Result<Vec<TableSize>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows total size of all indexes in the database.
+    pub async fn total_index_size(&self) -> Result<Vec<TotalIndexSize>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows total disk space used by tables and indexes.
+    pub async fn total_table_size(&self) -> Result<Vec<TotalTableSize>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Lists indexes that haven't been used or are rarely used.
+    pub async fn unused_indexes(
+        &self,
+        schema: Option<String>,
+    ) -> Result<Vec<UnusedIndexes>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows indexes that have identical definitions but different names.
+    pub async fn duplicate_indexes(&self) -> Result<Vec<DuplicateIndexes>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows statistics about VACUUM and ANALYZE operations.
+    pub async fn vacuum_stats(&self) -> Result<Vec<VacuumStats>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows statistics about shared buffer cache usage.
+    pub async fn buffercache_stats(&self) -> Result<Vec<BuffercacheStats>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows distribution of buffer cache usage by database objects.
+    pub async fn buffercache_usage(&self) -> Result<Vec<BuffercacheUsage>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows whether SSL is being used for current connections.
+    pub async fn ssl_used(&self) -> Result<Vec<SslUsed>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows information about current database connections and their states.
+    pub async fn connections(&self) -> Result<Vec<Connections>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Shows cache hit rates for both tables and indexes.
+    pub async fn cache_hit(&self, schema: Option<String>) -> Result<Vec<CacheHit>, PgExtrasError> {
+        self.get_rows(Some(schema_params(schema))).await
+    }
+
+    /// Shows current values of important PostgreSQL settings.
+    pub async fn db_settings(&self) -> Result<Vec<DbSettings>, PgExtrasError> {
+        self.get_rows(None).await
+    }
+
+    /// Runs a comprehensive set of diagnostic checks on the database.
+    /// Fails on invalid `PG_EXTRAS_DIAGNOSE_<KEY>` overrides, unless the thresholds were
+    /// replaced with `with_diagnose_config`.
+    pub async fn diagnose(&self) -> Result<Vec<CheckResult>, PgExtrasError> {
+        let mut diagnose_config = self.diagnose_config.clone();
+        if self.diagnose_env {
+            diagnose_config.apply_env()?;
+        }
+        run_diagnose(self, &diagnose_config).await
+    }
+
+    pub(crate) async fn get_rows<T: Query>(
+        &self,
+        params: Option<HashMap<QueryParam, String>>,
+    ) -> Result<Vec<T>, PgExtrasError> {
+        let mut params = params.unwrap_or_default();
+        if let Some(schema) = &self.default_schema {
+            params
+                .entry(QueryParam::Schema)
+                .or_insert_with(|| schema.clone());
+        }
+
+        get_rows(
+            Some(params),
+            Some(&self.capabilities),
+            &self.query_timeouts(T::name()),
+            &self.pool,
+        )
+        .await
+    }
+}
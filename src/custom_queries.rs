@@ -0,0 +1,490 @@
+use crate::diagnose::run::{CheckFuture, CheckOutcome, DiagnoseCheck, Severity};
+use crate::{fetch_with_timeouts, PgExtras, PgExtrasError, QueryKind, QueryOutput, Timeouts};
+use serde_json::Value;
+use sqlx::{Column, Executor, Pool, Postgres, Row};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs};
+
+/// Rows of the query as JSON objects, keeping the column order of the select list.
+const ROWS_QUERY: &str = "SELECT row_to_json(q)::text FROM (\n{sql}\n) q";
+
+/// Rows shown in the message of a failed check.
+const MESSAGE_ROWS: usize = 5;
+
+/// A query read from a `.sql` file, shown next to the built-in ones.
+///
+/// The file starts with a `/* description */` comment, which may be followed by
+/// `--` comments turning the query into a `diagnose` check:
+///
+/// ```sql
+/// /* Uploads without an owner */
+/// -- check: fail_if_rows > 0
+/// -- severity: critical
+/// -- recommendation: Delete orphaned uploads
+/// -- recommendation: They are left behind when a user is removed.
+///
+/// SELECT id, path FROM uploads WHERE user_id IS NULL
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomQuery {
+    /// File name without the `.sql` extension, e.g. `orphaned_uploads`.
+    pub name: String,
+    pub description: String,
+    pub sql: String,
+    pub check: Option<RowCheck>,
+}
+
+/// Fails the check when the number of returned rows matches the comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowCheck {
+    pub comparison: Comparison,
+    pub rows: usize,
+    /// Severity of a failed check, `warning` unless set with `-- severity:`.
+    pub severity: Severity,
+    /// Header of the recommendation, the description unless set with `-- recommendation:`.
+    pub header: String,
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl CustomQuery {
+    /// Parses the contents of `<name>.sql`.
+    pub fn parse(name: &str, contents: &str) -> Result<Self, PgExtrasError> {
+        let invalid = |message: String| {
+            PgExtrasError::InvalidConfig(format!("query '{}': {}", name, message))
+        };
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(invalid(
+                "names may only contain lowercase letters, digits and underscores".to_string(),
+            ));
+        }
+        if QueryKind::from_name(name).is_some() || name == "diagnose" {
+            return Err(invalid("the name is taken by a built-in query".to_string()));
+        }
+
+        let contents = contents.trim_start();
+        let (description, body) = contents
+            .strip_prefix("/*")
+            .and_then(|rest| rest.split_once("*/"))
+            .ok_or_else(|| invalid("expected a /* description */ header".to_string()))?;
+        let description = description.trim().trim_end_matches('.').to_string();
+
+        let mut check = None;
+        let mut severity = None;
+        let mut recommendation = Vec::new();
+        let mut sql_start = 0;
+        for line in body.split_inclusive('\n') {
+            if line.trim().is_empty() {
+                sql_start += line.len();
+                continue;
+            }
+            let Some(comment) = line.trim().strip_prefix("--") else {
+                break;
+            };
+            sql_start += line.len();
+            let Some((key, value)) = comment.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "check" => check = Some(parse_condition(value).map_err(invalid)?),
+                "severity" => severity = Some(parse_severity(value).map_err(invalid)?),
+                "recommendation" => recommendation.push(value.to_string()),
+                // other comments are kept as documentation of the query
+                _ => {}
+            }
+        }
+
+        let check = match check {
+            Some((comparison, rows)) => {
+                let mut recommendation = recommendation.into_iter();
+                Some(RowCheck {
+                    comparison,
+                    rows,
+                    severity: severity.unwrap_or(Severity::Warning),
+                    header: recommendation.next().unwrap_or_else(|| description.clone()),
+                    details: recommendation.collect(),
+                })
+            }
+            None if severity.is_some() || !recommendation.is_empty() => {
+                return Err(invalid(
+                    "severity and recommendation need a '-- check:' header".to_string(),
+                ));
+            }
+            None => None,
+        };
+
+        let sql = body[sql_start..].trim().trim_end_matches(';').trim_end();
+        if sql.is_empty() {
+            return Err(invalid("the file has no query".to_string()));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            description,
+            sql: sql.to_string(),
+            check,
+        })
+    }
+
+    /// Reads every `.sql` file of `dir`, sorted by name.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<Self>, PgExtrasError> {
+        let dir = dir.as_ref();
+        let read_error = |e: std::io::Error| {
+            PgExtrasError::InvalidConfig(format!("cannot read {}: {}", dir.display(), e))
+        };
+
+        let mut paths = fs::read_dir(dir)
+            .map_err(read_error)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(read_error)?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "sql"));
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                let contents = fs::read_to_string(path).map_err(|e| {
+                    PgExtrasError::InvalidConfig(format!("cannot read {}: {}", path.display(), e))
+                })?;
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                Self::parse(&name, &contents).map_err(|e| match e {
+                    PgExtrasError::InvalidConfig(message) => {
+                        PgExtrasError::InvalidConfig(format!("{}: {}", path.display(), message))
+                    }
+                    e => e,
+                })
+            })
+            .collect()
+    }
+
+    /// Runs the query with the timeouts the client has for its name.
+    pub async fn run(&self, client: &PgExtras) -> Result<QueryOutput, PgExtrasError> {
+        self.fetch(client.pool(), &client.query_timeouts(&self.name))
+            .await
+    }
+
+    async fn fetch(
+        &self,
+        pool: &Pool<Postgres>,
+        timeouts: &Timeouts,
+    ) -> Result<QueryOutput, PgExtrasError> {
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))?;
+
+        let sql = ROWS_QUERY.replace("{sql}", &self.sql);
+        let rows = fetch_with_timeouts(&mut conn, sqlx::query(&sql), timeouts)
+            .await
+            .map_err(|e| self.query_error(e, timeouts))?
+            .iter()
+            .map(|row| {
+                let json: String = row.try_get(0)?;
+                serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            })
+            .collect::<Result<Vec<Value>, _>>()
+            .map_err(|e| PgExtrasError::query_failed(&self.name, e))?;
+
+        let headers: Vec<String> = match rows.first() {
+            Some(Value::Object(row)) => row.keys().cloned().collect(),
+            // without rows the column names come from the prepared statement
+            _ => (&mut *conn)
+                .describe(&self.sql)
+                .await
+                .map_err(|e| PgExtrasError::query_failed(&self.name, e))?
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect(),
+        };
+        let cells = rows
+            .iter()
+            .map(|row| headers.iter().map(|header| cell(&row[header])).collect())
+            .collect();
+
+        Ok(QueryOutput {
+            description: self.description.clone(),
+            headers,
+            rows,
+            cells,
+        })
+    }
+
+    /// Unlike the built-in queries, an undefined column is a mistake in the file rather
+    /// than a difference between server versions, so only timeouts and privileges are told apart.
+    fn query_error(&self, error: sqlx::Error, timeouts: &Timeouts) -> PgExtrasError {
+        let query = self.name.clone();
+        match crate::sqlstate(&error).as_deref() {
+            Some("57014") if timeouts.statement_timeout.is_some() => {
+                PgExtrasError::Timeout { query }
+            }
+            Some("55P03") if timeouts.lock_timeout.is_some() => PgExtrasError::Timeout { query },
+            Some("42501") => PgExtrasError::InsufficientPrivilege {
+                query,
+                message: crate::database_message(&error),
+                source: Arc::new(error),
+            },
+            _ => PgExtrasError::query_failed(&self.name, error),
+        }
+    }
+}
+
+impl DiagnoseCheck for CustomQuery {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run<'a>(&'a self, client: &'a PgExtras) -> CheckFuture<'a> {
+        Box::pin(async move {
+            let output = CustomQuery::run(self, client).await?;
+            let count = output.rows.len();
+            let mut message = match count {
+                1 => "1 row returned".to_string(),
+                count => format!("{} rows returned", count),
+            };
+
+            let Some(check) = self.check.as_ref().filter(|check| check.fails(count)) else {
+                return Ok(CheckOutcome {
+                    severity: Severity::Ok,
+                    message,
+                });
+            };
+            for cells in output.cells.iter().take(MESSAGE_ROWS) {
+                message.push_str(&format!("\n{}", cells.join(", ")));
+            }
+            if count > MESSAGE_ROWS {
+                message.push_str(&format!("\n… and {} more", count - MESSAGE_ROWS));
+            }
+            Ok(CheckOutcome {
+                severity: check.severity,
+                message,
+            })
+        })
+    }
+
+    fn recommendation(&self) -> (&str, Vec<&str>) {
+        match &self.check {
+            Some(check) => (
+                check.header.as_str(),
+                check.details.iter().map(String::as_str).collect(),
+            ),
+            None => (&self.description, vec![]),
+        }
+    }
+}
+
+impl RowCheck {
+    /// Whether `rows` returned rows fail the check.
+    pub fn fails(&self, rows: usize) -> bool {
+        match self.comparison {
+            Comparison::Greater => rows > self.rows,
+            Comparison::GreaterOrEqual => rows >= self.rows,
+            Comparison::Less => rows < self.rows,
+            Comparison::LessOrEqual => rows <= self.rows,
+            Comparison::Equal => rows == self.rows,
+            Comparison::NotEqual => rows != self.rows,
+        }
+    }
+}
+
+impl fmt::Display for RowCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fail_if_rows {} {}", self.comparison, self.rows)
+    }
+}
+
+impl Comparison {
+    const ALL: [Comparison; 6] = [
+        // two character operators first, so `>=` is not read as `>`
+        Comparison::GreaterOrEqual,
+        Comparison::LessOrEqual,
+        Comparison::NotEqual,
+        Comparison::Greater,
+        Comparison::Less,
+        Comparison::Equal,
+    ];
+
+    pub fn operator(&self) -> &'static str {
+        match self {
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.operator())
+    }
+}
+
+/// Parses `fail_if_rows > 0`.
+fn parse_condition(value: &str) -> Result<(Comparison, usize), String> {
+    let invalid = || {
+        format!(
+            "invalid check '{}', expected e.g. 'fail_if_rows > 0'",
+            value
+        )
+    };
+
+    let condition = value
+        .strip_prefix("fail_if_rows")
+        .ok_or_else(invalid)?
+        .trim_start();
+    let (comparison, rows) = Comparison::ALL
+        .iter()
+        .find_map(|comparison| {
+            condition
+                .strip_prefix(comparison.operator())
+                .map(|rows| (*comparison, rows))
+        })
+        .ok_or_else(invalid)?;
+    let rows = rows.trim().parse().map_err(|_| invalid())?;
+    Ok((comparison, rows))
+}
+
+fn parse_severity(value: &str) -> Result<Severity, String> {
+    match value {
+        "info" => Ok(Severity::Info),
+        "warning" => Ok(Severity::Warning),
+        "critical" => Ok(Severity::Critical),
+        _ => Err(format!(
+            "invalid severity '{}', expected info, warning or critical",
+            value
+        )),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_description_and_check_headers() {
+        let query = CustomQuery::parse(
+            "orphaned_uploads",
+            "/* Uploads without an owner. */
+-- check: fail_if_rows >= 2
+-- severity: critical
+-- recommendation: Delete orphaned uploads
+-- recommendation: They are left behind when a user is removed.
+-- owner: storage team
+
+SELECT id, path
+FROM uploads
+WHERE user_id IS NULL;
+",
+        )
+        .unwrap();
+
+        assert_eq!(query.description, "Uploads without an owner");
+        assert_eq!(
+            query.sql,
+            "SELECT id, path\nFROM uploads\nWHERE user_id IS NULL"
+        );
+        let check = query.check.unwrap();
+        assert_eq!(check.to_string(), "fail_if_rows >= 2");
+        assert_eq!(check.severity, Severity::Critical);
+        assert_eq!(check.header, "Delete orphaned uploads");
+        assert_eq!(
+            check.details,
+            vec!["They are left behind when a user is removed."]
+        );
+        assert!(!check.fails(1));
+        assert!(check.fails(2));
+
+        let query =
+            CustomQuery::parse("roles", "/* Roles */\nSELECT rolname FROM pg_roles").unwrap();
+        assert_eq!(query.check, None);
+
+        let check =
+            CustomQuery::parse("roles", "/* Roles */\n-- check: fail_if_rows = 0\nSELECT 1")
+                .unwrap()
+                .check
+                .unwrap();
+        assert_eq!(check.severity, Severity::Warning);
+        assert_eq!(check.header, "Roles");
+        assert!(check.fails(0));
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        for (name, contents) in [
+            ("roles", "SELECT 1"),
+            ("roles", "/* Roles */"),
+            ("roles", "/* Roles */\n-- check: rows > 0\nSELECT 1"),
+            (
+                "roles",
+                "/* Roles */\n-- check: fail_if_rows > -1\nSELECT 1",
+            ),
+            ("roles", "/* Roles */\n-- severity: critical\nSELECT 1"),
+            (
+                "roles",
+                "/* Roles */\n-- check: fail_if_rows > 0\n-- severity: fatal\nSELECT 1",
+            ),
+            ("cache_hit", "/* Cache hit */\nSELECT 1"),
+            ("Roles", "/* Roles */\nSELECT 1"),
+        ] {
+            assert!(
+                matches!(
+                    CustomQuery::parse(name, contents),
+                    Err(PgExtrasError::InvalidConfig(_))
+                ),
+                "{}: {}",
+                name,
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn loads_sql_files_of_a_directory() {
+        let dir = std::env::temp_dir().join(format!("pg-extras-queries-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b_roles.sql"), "/* Roles */\nSELECT 1").unwrap();
+        fs::write(dir.join("a_tables.sql"), "/* Tables */\nSELECT 2").unwrap();
+        fs::write(dir.join("notes.txt"), "not a query").unwrap();
+
+        let names = CustomQuery::load_dir(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|query| query.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a_tables", "b_roles"]);
+
+        fs::write(dir.join("c_broken.sql"), "SELECT 3").unwrap();
+        let error = CustomQuery::load_dir(&dir).unwrap_err().to_string();
+        assert!(error.contains("c_broken.sql"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use crate::diagnose::run::Severity;
+use crate::PgExtrasError;
+use std::env;
+
+/// Thresholds used by the diagnose checks. Crossing a threshold gives a warning, crossing
+/// its `_critical` counterpart a critical result.
+///
+/// Read from the `diagnose` table of a config profile, each key can be overridden with a
+/// `PG_EXTRAS_DIAGNOSE_<KEY>` environment variable or `set`, e.g. by `--threshold`.
+/// Diagnose rejects thresholds where the critical one is not past the warning one.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiagnoseConfig {
+    /// Minimum table cache hit ratio.
+    pub table_cache_hit: f64,
+    /// Table cache hit ratio below which the result is critical.
+    pub table_cache_hit_critical: f64,
+    /// Minimum index cache hit ratio.
+    pub index_cache_hit: f64,
+    /// Index cache hit ratio below which the result is critical.
+    pub index_cache_hit_critical: f64,
+    /// Unused indexes smaller than this are ignored, in bytes.
+    pub unused_indexes_min_size: i64,
+    /// Unused indexes from this size on are critical, in bytes.
+    pub unused_indexes_min_size_critical: i64,
+    /// Indexes on relations smaller than this are ignored, in MB.
+    pub null_indexes_min_size_mb: i64,
+    /// Minimum fraction of NULL values in an indexed column.
+    pub null_indexes_null_frac: f64,
+    /// Fraction of NULL values from which the result is critical.
+    pub null_indexes_null_frac_critical: f64,
+    /// Minimum bloat factor of a table or index.
+    pub bloat: f64,
+    /// Bloat factor from which the result is critical.
+    pub bloat_critical: f64,
+    /// Minimum fraction of the total execution time used by a single query.
+    pub outliers_exec_ratio: f64,
+    /// Fraction of the total execution time from which the result is critical.
+    pub outliers_exec_ratio_critical: f64,
+}
+
+/// The settings a check was evaluated against, e.g. `table_cache_hit` and
+/// `table_cache_hit_critical`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Threshold {
+    pub name: &'static str,
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Default for DiagnoseConfig {
+    fn default() -> Self {
+        Self {
+            table_cache_hit: 0.985,
+            table_cache_hit_critical: 0.95,
+            index_cache_hit: 0.985,
+            index_cache_hit_critical: 0.95,
+            unused_indexes_min_size: 1_000_000,            // 1 MB
+            unused_indexes_min_size_critical: 100_000_000, // 100 MB
+            null_indexes_min_size_mb: 1,
+            null_indexes_null_frac: 0.5,
+            null_indexes_null_frac_critical: 0.9,
+            bloat: 10.0,
+            bloat_critical: 50.0,
+            outliers_exec_ratio: 0.33,
+            outliers_exec_ratio_critical: 0.66,
+        }
+    }
+}
+
+impl DiagnoseConfig {
+    pub const KEYS: &'static [&'static str] = &[
+        "table_cache_hit",
+        "table_cache_hit_critical",
+        "index_cache_hit",
+        "index_cache_hit_critical",
+        "unused_indexes_min_size",
+        "unused_indexes_min_size_critical",
+        "null_indexes_min_size_mb",
+        "null_indexes_null_frac",
+        "null_indexes_null_frac_critical",
+        "bloat",
+        "bloat_critical",
+        "outliers_exec_ratio",
+        "outliers_exec_ratio_critical",
+    ];
+
+    /// Sets the threshold called `key` from its text, e.g. `set("table_cache_hit", "0.99")`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), PgExtrasError> {
+        let invalid = || {
+            PgExtrasError::InvalidConfig(format!(
+                "invalid value '{}' for diagnose threshold '{}'",
+                value, key
+            ))
+        };
+        let float = || value.trim().parse::<f64>().map_err(|_| invalid());
+        let integer = || value.trim().parse::<i64>().map_err(|_| invalid());
+
+        match key {
+            "table_cache_hit" => self.table_cache_hit = float()?,
+            "table_cache_hit_critical" => self.table_cache_hit_critical = float()?,
+            "index_cache_hit" => self.index_cache_hit = float()?,
+            "index_cache_hit_critical" => self.index_cache_hit_critical = float()?,
+            "unused_indexes_min_size" => self.unused_indexes_min_size = integer()?,
+            "unused_indexes_min_size_critical" => {
+                self.unused_indexes_min_size_critical = integer()?
+            }
+            "null_indexes_min_size_mb" => self.null_indexes_min_size_mb = integer()?,
+            "null_indexes_null_frac" => self.null_indexes_null_frac = float()?,
+            "null_indexes_null_frac_critical" => self.null_indexes_null_frac_critical = float()?,
+            "bloat" => self.bloat = float()?,
+            "bloat_critical" => self.bloat_critical = float()?,
+            "outliers_exec_ratio" => self.outliers_exec_ratio = float()?,
+            "outliers_exec_ratio_critical" => self.outliers_exec_ratio_critical = float()?,
+            _ => {
+                return Err(PgExtrasError::InvalidConfig(format!(
+                    "unknown diagnose threshold '{}', expected one of {}",
+                    key,
+                    Self::KEYS.join(", ")
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Overrides the thresholds set in `PG_EXTRAS_DIAGNOSE_<KEY>`, e.g.
+    /// `PG_EXTRAS_DIAGNOSE_TABLE_CACHE_HIT=0.99`.
+    pub fn apply_env(&mut self) -> Result<(), PgExtrasError> {
+        self.apply_overrides(|name| env::var(name).ok())
+    }
+
+    /// Like `apply_env`, looking up the `PG_EXTRAS_DIAGNOSE_<KEY>` variables with `lookup`.
+    pub fn apply_overrides(
+        &mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), PgExtrasError> {
+        for key in Self::KEYS {
+            if let Some(value) = lookup(&format!("PG_EXTRAS_DIAGNOSE_{}", key.to_uppercase())) {
+                self.set(key, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that each critical threshold is past its warning one, once every override
+    /// is applied. Otherwise values passing the warning threshold would be graded critical.
+    pub fn validate(&self) -> Result<(), PgExtrasError> {
+        // lower values are worse
+        let below = [
+            (
+                "table_cache_hit",
+                self.table_cache_hit,
+                self.table_cache_hit_critical,
+            ),
+            (
+                "index_cache_hit",
+                self.index_cache_hit,
+                self.index_cache_hit_critical,
+            ),
+        ];
+        // higher values are worse
+        let above = [
+            (
+                "unused_indexes_min_size",
+                self.unused_indexes_min_size as f64,
+                self.unused_indexes_min_size_critical as f64,
+            ),
+            (
+                "null_indexes_null_frac",
+                self.null_indexes_null_frac,
+                self.null_indexes_null_frac_critical,
+            ),
+            ("bloat", self.bloat, self.bloat_critical),
+            (
+                "outliers_exec_ratio",
+                self.outliers_exec_ratio,
+                self.outliers_exec_ratio_critical,
+            ),
+        ];
+
+        let inverted = |key: &str, warning: f64, critical: f64, order: &str| {
+            PgExtrasError::InvalidConfig(format!(
+                "diagnose threshold '{}_critical' ({}) must not be {} '{}' ({})",
+                key, critical, order, key, warning
+            ))
+        };
+        for (key, warning, critical) in below {
+            if critical > warning {
+                return Err(inverted(key, warning, critical, "above"));
+            }
+        }
+        for (key, warning, critical) in above {
+            if critical < warning {
+                return Err(inverted(key, warning, critical, "below"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Threshold {
+    pub fn new(name: &'static str, warning: f64, critical: f64) -> Self {
+        Self {
+            name,
+            warning,
+            critical,
+        }
+    }
+
+    /// Severity of `value` when lower values are worse, e.g. a cache hit ratio.
+    pub fn severity_below(&self, value: f64) -> Severity {
+        if value < self.critical {
+            Severity::Critical
+        } else if value < self.warning {
+            Severity::Warning
+        } else {
+            Severity::Ok
+        }
+    }
+
+    /// Severity of `value` when higher values are worse, e.g. a bloat factor.
+    pub fn severity_above(&self, value: f64) -> Severity {
+        if value >= self.critical {
+            Severity::Critical
+        } else if value >= self.warning {
+            Severity::Warning
+        } else {
+            Severity::Ok
+        }
+    }
+}
+
+impl std::fmt::Display for Threshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: warning {}, critical {}",
+            self.name, self.warning, self.critical
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_thresholds_by_name() {
+        let mut config = DiagnoseConfig::default();
+        config.set("table_cache_hit", "0.995").unwrap();
+        config.set("unused_indexes_min_size", "5000000").unwrap();
+        assert_eq!(config.table_cache_hit, 0.995);
+        assert_eq!(config.unused_indexes_min_size, 5_000_000);
+
+        assert!(matches!(
+            config.set("unused_indexes_min_size", "1.5"),
+            Err(PgExtrasError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            config.set("table_hit", "0.9"),
+            Err(PgExtrasError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn applies_overrides_by_variable_name() {
+        let lookup = |name: &str| match name {
+            "PG_EXTRAS_DIAGNOSE_BLOAT_CRITICAL" => Some("70".to_string()),
+            "PG_EXTRAS_DIAGNOSE_TABLE_CACHE_HIT" => Some("0.99".to_string()),
+            _ => None,
+        };
+        let mut config = DiagnoseConfig::default();
+        config.apply_overrides(lookup).unwrap();
+        assert_eq!(config.bloat_critical, 70.0);
+        assert_eq!(config.table_cache_hit, 0.99);
+        assert_eq!(config.bloat, DiagnoseConfig::default().bloat);
+
+        let mut config = DiagnoseConfig::default();
+        let error = config
+            .apply_overrides(|name| (name == "PG_EXTRAS_DIAGNOSE_BLOAT").then(|| "abc".to_string()))
+            .unwrap_err();
+        assert!(matches!(error, PgExtrasError::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn rejects_inverted_thresholds() {
+        assert!(DiagnoseConfig::default().validate().is_ok());
+
+        // the critical default of 0.95 is above a warning threshold of 0.9
+        let mut config = DiagnoseConfig::default();
+        config.set("table_cache_hit", "0.9").unwrap();
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("table_cache_hit_critical"), "{}", error);
+        config.set("table_cache_hit_critical", "0.8").unwrap();
+        assert!(config.validate().is_ok());
+
+        for (key, value) in [
+            ("index_cache_hit_critical", "0.99"),
+            ("unused_indexes_min_size", "500000000"),
+            ("null_indexes_null_frac_critical", "0.4"),
+            ("bloat", "60"),
+            ("outliers_exec_ratio_critical", "0.2"),
+        ] {
+            let mut config = DiagnoseConfig::default();
+            config.set(key, value).unwrap();
+            assert!(
+                matches!(config.validate(), Err(PgExtrasError::InvalidConfig(_))),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn grades_values_against_thresholds() {
+        let cache_hit = Threshold::new("table_cache_hit", 0.985, 0.95);
+        assert_eq!(cache_hit.severity_below(0.99), Severity::Ok);
+        assert_eq!(cache_hit.severity_below(0.96), Severity::Warning);
+        assert_eq!(cache_hit.severity_below(0.9), Severity::Critical);
+
+        let bloat = Threshold::new("bloat", 10.0, 50.0);
+        assert_eq!(bloat.severity_above(2.0), Severity::Ok);
+        assert_eq!(bloat.severity_above(10.0), Severity::Warning);
+        assert_eq!(bloat.severity_above(90.0), Severity::Critical);
+    }
+}
@@ -1,4 +1,4 @@
+pub mod config;
 pub mod recommendation;
 pub mod report;
 pub mod run;
-pub mod size_parser;
@@ -1,48 +1,342 @@
-use crate::diagnose::recommendation;
-use crate::diagnose::run::CheckResult;
+use crate::diagnose::run::{health_score, Check, CheckResult, Severity};
+use crate::output::{write_output, OutputFormat};
+use crate::QueryOutput;
 use prettytable::{Cell, Row as TableRow, Table};
+use serde_json::json;
+use std::io::{self, Write};
 
 pub fn render_diagnose_report(items: Vec<CheckResult>) {
+    report_table(&items).printstd();
+}
+
+/// Writes the report in the given format, worst results first. Non-table formats use the
+/// columns `check`, `severity`, `message`, `recommendation` and `threshold`, in that order.
+pub fn write_diagnose_report<W: Write>(
+    items: &[CheckResult],
+    format: OutputFormat,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            report_table(items).print(writer)?;
+            Ok(())
+        }
+        _ => write_output(&diagnose_output(items), format, writer),
+    }
+}
+
+/// Report rows with their types erased, `recommendation` is `null` for passed checks
+/// and `threshold` for checks without one.
+pub fn diagnose_output(items: &[CheckResult]) -> QueryOutput {
+    let items = by_severity(items);
+    QueryOutput {
+        description: report_title(&items),
+        headers: [
+            "check",
+            "severity",
+            "message",
+            "recommendation",
+            "threshold",
+        ]
+        .iter()
+        .map(|header| header.to_string())
+        .collect(),
+        rows: items
+            .iter()
+            .map(|item| {
+                json!({
+                    "check": item.check.to_string(),
+                    "severity": item.severity,
+                    "message": item.message,
+                    "recommendation": recommendation_text(item),
+                    "threshold": item.threshold,
+                })
+            })
+            .collect(),
+        cells: items
+            .iter()
+            .map(|item| {
+                vec![
+                    item.check.to_string(),
+                    item.severity.to_string(),
+                    item.message.clone(),
+                    recommendation_text(item).unwrap_or_else(|| "None".to_string()),
+                    item.threshold
+                        .as_ref()
+                        .map_or_else(|| "None".to_string(), |threshold| threshold.to_string()),
+                ]
+            })
+            .collect(),
+    }
+}
+
+/// The results ordered from the most severe, keeping the check order within a severity.
+pub fn by_severity(items: &[CheckResult]) -> Vec<CheckResult> {
+    let mut items = items.to_vec();
+    items.sort_by_key(|item| std::cmp::Reverse(item.severity));
+    items
+}
+
+fn report_title(items: &[CheckResult]) -> String {
+    format!("Diagnose Report, health score {}/100", health_score(items))
+}
+
+fn recommendation_text(item: &CheckResult) -> Option<String> {
+    if item.ok() {
+        return None;
+    }
+    Some(check_recommendation(&item.check))
+}
+
+fn check_recommendation(check: &Check) -> String {
+    let (header, details) = check.recommendation();
+    // build the recommendation text by concatenating the header and details with bullet points
+    format!(
+        "{}:\n{}",
+        header,
+        details
+            .iter()
+            .map(|detail| format!("• {}", detail))
+            .collect::<Vec<String>>()
+            .join("\n")
+    )
+}
+
+/// Writes the report as JUnit XML, one test case per check. Warnings and critical results
+/// are failures holding the message and recommendation.
+pub fn write_junit_report<W: Write>(items: &[CheckResult], writer: &mut W) -> io::Result<()> {
+    let failures = items.iter().filter(|item| !item.ok()).count();
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<testsuites name="pg-extras diagnose" tests="{}" failures="{}">"#,
+        items.len(),
+        failures
+    )?;
+    writeln!(
+        writer,
+        r#"  <testsuite name="diagnose" tests="{}" failures="{}" errors="0" skipped="0">"#,
+        items.len(),
+        failures
+    )?;
+    for item in items {
+        writeln!(
+            writer,
+            r#"    <testcase classname="pg_extras.diagnose" name="{}">"#,
+            xml_escape(&item.check.to_string())
+        )?;
+        match recommendation_text(item) {
+            Some(recommendation) => writeln!(
+                writer,
+                r#"      <failure type="{}" message="{}">{}</failure>"#,
+                item.severity,
+                xml_escape(item.message.lines().next().unwrap_or_default()),
+                xml_escape(&format!("{}\n\n{}", item.message, recommendation))
+            )?,
+            None => writeln!(
+                writer,
+                "      <system-out>{}</system-out>",
+                xml_escape(&item.message)
+            )?,
+        }
+        writeln!(writer, "    </testcase>")?;
+    }
+    writeln!(writer, "  </testsuite>")?;
+    writeln!(writer, "</testsuites>")
+}
+
+/// Writes the report as a SARIF 2.1.0 log for code scanning. Every check is a rule with
+/// its recommendation as help, warnings and critical results are reported against it.
+/// `location` is the artifact results point at, e.g. the database name or a snapshot file,
+/// code scanning rejects results without one.
+pub fn write_sarif_report<W: Write>(
+    items: &[CheckResult],
+    location: &str,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut rules: Vec<serde_json::Value> = Vec::new();
+    for item in items {
+        let id = item.check.to_string();
+        // custom checks may share a name, rule ids must be unique
+        if rules.iter().any(|rule| rule["id"] == id) {
+            continue;
+        }
+        let (header, _) = item.check.recommendation();
+        rules.push(json!({
+            "id": id,
+            "shortDescription": { "text": header },
+            "help": { "text": check_recommendation(&item.check) },
+        }));
+    }
+    let results = items
+        .iter()
+        .filter(|item| !item.ok())
+        .map(|item| {
+            json!({
+                "ruleId": item.check.to_string(),
+                "level": match item.severity {
+                    Severity::Critical => "error",
+                    _ => "warning",
+                },
+                "message": { "text": item.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": location },
+                    },
+                    "logicalLocations": [{
+                        "name": item.check.to_string(),
+                        "fullyQualifiedName": format!("{}/{}", location, item.check),
+                    }],
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pg-extras",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/pawurb/pg-extras-rs",
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut *writer, &log)?;
+    writeln!(writer)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn report_table(items: &[CheckResult]) -> Table {
     let term_width = textwrap::termwidth();
     let recommendation_width = term_width / 3; // need to adjust this to make the recommendation text wrap nicely
 
     let mut table = Table::new();
 
-    let mut header_cell = Cell::new("Diagnose Report").style_spec("bH3");
+    let items = by_severity(items);
+    let mut header_cell = Cell::new(&report_title(&items)).style_spec("bH3");
     header_cell.align(prettytable::format::Alignment::CENTER);
     table.set_titles(TableRow::new(vec![header_cell]));
 
     table.add_row(row!["Check", "Message", "Recommendation"]);
 
-    for item in items {
-        let style = if item.ok { "Fg" } else { "Fr" };
-
-        let status_and_name = format!("[{}] - {}", if item.ok { "√" } else { "x" }, item.check);
-
-        // get the recommendation for the check
-        let recommendation = if item.ok {
-            "None".to_string()
-        } else {
-            let (header, details) = recommendation::Recommendations.get(&item.check).unwrap();
-            // build the recommendation text by concatenating the header and details with bullet points
-            format!(
-                "{}:\n{}",
-                header,
-                details
-                    .iter()
-                    .map(|detail| format!("• {}", detail))
-                    .collect::<Vec<String>>()
-                    .join("\n")
-            )
+    for item in &items {
+        let (style, status) = match item.severity {
+            Severity::Ok => ("Fg", "√"),
+            Severity::Info => ("Fb", "i"),
+            Severity::Warning => ("Fy", "!"),
+            Severity::Critical => ("Fr", "x"),
+        };
+
+        let status_and_name = format!("[{}] - {}", status, item.check);
+
+        let recommendation = recommendation_text(item).unwrap_or_else(|| "None".to_string());
+        let message = match &item.threshold {
+            Some(threshold) => format!("{}\nThreshold: {}", item.message, threshold),
+            None => item.message.clone(),
         };
 
         table.add_row(TableRow::new(vec![
             Cell::new(status_and_name.as_str()).style_spec(style),
-            Cell::new(item.message.as_str()).style_spec(style),
+            Cell::new(message.as_str()).style_spec(style),
             Cell::new(textwrap::fill(&recommendation, recommendation_width).as_str())
                 .style_spec(style),
         ]));
     }
 
-    table.printstd();
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnose::config::Threshold;
+
+    fn items() -> Vec<CheckResult> {
+        vec![
+            CheckResult {
+                severity: Severity::Ok,
+                message: "Table cache hit rate is correct: 0.9990".to_string(),
+                check: Check::TableCacheHit,
+                threshold: Some(Threshold::new("table_cache_hit", 0.995, 0.95)),
+            },
+            CheckResult {
+                severity: Severity::Warning,
+                message: "Bloat detected".to_string(),
+                check: Check::Bloat,
+                threshold: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn structured_report_columns() {
+        let items = items();
+        let mut buffer = Vec::new();
+        write_diagnose_report(&items, OutputFormat::Ndjson, &mut buffer).unwrap();
+        let lines = String::from_utf8(buffer).unwrap();
+        let mut lines = lines.lines();
+
+        // sorted by severity
+        assert!(lines.next().unwrap().starts_with(
+            r#"{"check":"bloat","severity":"warning","message":"Bloat detected","recommendation":""#
+        ));
+        assert_eq!(
+            lines.next().unwrap(),
+            r#"{"check":"table_cache_hit","severity":"ok","message":"Table cache hit rate is correct: 0.9990","recommendation":null,"threshold":{"name":"table_cache_hit","warning":0.995,"critical":0.95}}"#
+        );
+    }
+
+    #[test]
+    fn ci_reports_list_failed_checks() {
+        let mut items = items();
+        items[1].message = "Bloat <detected> & growing".to_string();
+
+        let mut junit = Vec::new();
+        write_junit_report(&items, &mut junit).unwrap();
+        let junit = String::from_utf8(junit).unwrap();
+        assert!(junit.contains(r#"<testsuites name="pg-extras diagnose" tests="2" failures="1">"#));
+        assert!(junit.contains(
+            r#"<failure type="warning" message="Bloat &lt;detected&gt; &amp; growing">"#
+        ));
+        assert!(junit.contains("Get rid of unnecessary bloat"));
+
+        // a second check named like the first one adds no rule
+        items.push(items[1].clone());
+        let mut sarif = Vec::new();
+        write_sarif_report(&items, "app_production", &mut sarif).unwrap();
+        let sarif: serde_json::Value = serde_json::from_slice(&sarif).unwrap();
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 2);
+        assert_eq!(run["results"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            run["results"][0],
+            json!({
+                "ruleId": "bloat",
+                "level": "warning",
+                "message": { "text": "Bloat <detected> & growing" },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "app_production" },
+                    },
+                    "logicalLocations": [{
+                        "name": "bloat",
+                        "fullyQualifiedName": "app_production/bloat",
+                    }],
+                }],
+            })
+        );
+    }
 }
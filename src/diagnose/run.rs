@@ -1,19 +1,16 @@
-use crate::diagnose::size_parser::to_bytes;
+use crate::diagnose::config::{DiagnoseConfig, Threshold};
+use crate::diagnose::recommendation::Recommendations;
+use crate::queries::shared::{format_bytes, format_percent};
 use crate::{
-    bloat, cache_hit, duplicate_indexes, extensions, null_indexes, outliers, ssl_used,
-    unused_indexes, Extensions, PgExtrasError,
+    Bloat, CacheHit, DuplicateIndexes, NullIndexes, Outliers, PgExtras, PgExtrasError, Query,
+    QueryParam, ServerCapabilities, SslUsed, UnusedIndexes,
 };
 use serde_json::Value;
-use sqlx::types::BigDecimal;
-use sqlx::{Pool, Postgres};
-
-const TABLE_CACHE_HIT_MIN: f32 = 0.985;
-const INDEX_CACHE_HIT_MIN: f32 = 0.985;
-const UNUSED_INDEXES_MIN_SIZE_BYTES: u64 = 1_000_000; // 1 MB
-const NULL_INDEXES_MIN_SIZE_MB: &str = "1"; // 1 MB
-const NULL_MIN_NULL_FRAC_PERCENT: f64 = 50.0; // 50%
-const BLOAT_MIN_VALUE: f64 = 10.0;
-const OUTLIERS_MIN_EXEC_RATIO: f64 = 33.0; // 33%
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, serde::Serialize)]
 pub enum Check {
@@ -25,23 +22,87 @@ pub enum Check {
     Bloat,
     DuplicateIndexes,
     Outliers,
+    /// A `DiagnoseCheck` registered by the user, with its recommendation.
+    Custom {
+        name: String,
+        header: String,
+        details: Vec<String>,
+    },
+}
+
+/// How bad a check result is, ordered from best to worst.
+#[derive(
+    Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Ok,
+    /// Nothing to fix, or the check could not tell.
+    Info,
+    Warning,
+    Critical,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct CheckResult {
-    pub ok: bool,
+    pub severity: Severity,
     pub message: String,
     pub check: Check,
+    /// The setting the check was evaluated against, checks without one have `None`.
+    pub threshold: Option<Threshold>,
 }
 
 impl CheckResult {
     pub fn to_json(&self) -> Value {
         serde_json::to_value(self).unwrap()
     }
+
+    /// Whether the check passed, `Info` results pass.
+    pub fn ok(&self) -> bool {
+        self.severity <= Severity::Info
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+/// Weighted share of the checks that passed, from 0 to 100. Warnings count half.
+pub fn health_score(results: &[CheckResult]) -> u8 {
+    let total = results
+        .iter()
+        .map(|result| result.check.weight())
+        .sum::<u32>();
+    if total == 0 {
+        return 100;
+    }
+
+    let passed = results
+        .iter()
+        .map(|result| {
+            let weight = result.check.weight() as f64;
+            match result.severity {
+                Severity::Ok | Severity::Info => weight,
+                Severity::Warning => weight / 2.0,
+                Severity::Critical => 0.0,
+            }
+        })
+        .sum::<f64>();
+    (passed * 100.0 / total as f64).round() as u8
 }
 
 impl std::fmt::Display for Check {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Self::Custom { name, .. } = self {
+            return f.write_str(name);
+        }
         let name = format!("{:?}", self);
         let snake_case_name = name
             .chars()
@@ -58,7 +119,227 @@ impl std::fmt::Display for Check {
     }
 }
 
-pub async fn run_diagnose(pool: &Pool<Postgres>) -> Result<Vec<CheckResult>, PgExtrasError> {
+impl Check {
+    /// The settings of `config` the check is evaluated against, if it has any.
+    pub fn threshold(&self, config: &DiagnoseConfig) -> Option<Threshold> {
+        let (name, warning, critical) = match self {
+            Self::TableCacheHit => (
+                "table_cache_hit",
+                config.table_cache_hit,
+                config.table_cache_hit_critical,
+            ),
+            Self::IndexCacheHit => (
+                "index_cache_hit",
+                config.index_cache_hit,
+                config.index_cache_hit_critical,
+            ),
+            Self::UnusedIndexes => (
+                "unused_indexes_min_size",
+                config.unused_indexes_min_size as f64,
+                config.unused_indexes_min_size_critical as f64,
+            ),
+            Self::NullIndexes => (
+                "null_indexes_null_frac",
+                config.null_indexes_null_frac,
+                config.null_indexes_null_frac_critical,
+            ),
+            Self::Bloat => ("bloat", config.bloat, config.bloat_critical),
+            Self::Outliers => (
+                "outliers_exec_ratio",
+                config.outliers_exec_ratio,
+                config.outliers_exec_ratio_critical,
+            ),
+            Self::SslUsed | Self::DuplicateIndexes | Self::Custom { .. } => return None,
+        };
+        Some(Threshold::new(name, warning, critical))
+    }
+
+    /// Header and details shown when the check does not pass.
+    pub fn recommendation(&self) -> (&str, Vec<&str>) {
+        match self {
+            Self::Custom {
+                header, details, ..
+            } => (header, details.iter().map(String::as_str).collect()),
+            check => {
+                let (header, details) = &Recommendations[check];
+                (header, details.clone())
+            }
+        }
+    }
+
+    /// How much the check counts towards the health score.
+    pub fn weight(&self) -> u32 {
+        match self {
+            Self::SslUsed | Self::TableCacheHit => 3,
+            Self::IndexCacheHit | Self::Bloat | Self::Outliers => 2,
+            Self::UnusedIndexes
+            | Self::NullIndexes
+            | Self::DuplicateIndexes
+            | Self::Custom { .. } => 1,
+        }
+    }
+}
+
+/// Future returned by `DiagnoseCheck::run`.
+pub type CheckFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<CheckOutcome, PgExtrasError>> + Send + 'a>>;
+
+/// What a `DiagnoseCheck` found.
+#[derive(Debug, Clone)]
+pub struct CheckOutcome {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl CheckOutcome {
+    /// A check returning an error is reported as a critical result, so it doesn't hide the
+    /// results of the other checks.
+    fn failed(error: PgExtrasError) -> Self {
+        Self {
+            severity: Severity::Critical,
+            message: format!("Check failed: {}", error),
+        }
+    }
+}
+
+/// A check run by `diagnose` next to the built-in ones, e.g. for rules specific to a schema.
+/// Register it with `PgExtras::with_check`, it is reported like the built-in checks.
+///
+/// ```no_run
+/// use pg_extras::diagnose::run::{CheckFuture, CheckOutcome, DiagnoseCheck, Severity};
+/// use pg_extras::{PgExtras, PgExtrasError};
+///
+/// struct BillingPrimaryKeys;
+///
+/// impl DiagnoseCheck for BillingPrimaryKeys {
+///     fn name(&self) -> &str {
+///         "billing_primary_keys"
+///     }
+///
+///     fn run<'a>(&'a self, client: &'a PgExtras) -> CheckFuture<'a> {
+///         Box::pin(async move {
+///             let tables: Vec<String> = sqlx::query_scalar(
+///                 "SELECT relname::text FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace
+///                  WHERE nspname = 'billing' AND relkind = 'r' AND NOT EXISTS (
+///                      SELECT 1 FROM pg_constraint WHERE conrelid = c.oid AND contype = 'p')",
+///             )
+///             .fetch_all(client.pool())
+///             .await
+///             .map_err(|e| PgExtrasError::Other(e.to_string()))?;
+///
+///             Ok(match tables.is_empty() {
+///                 true => CheckOutcome {
+///                     severity: Severity::Ok,
+///                     message: "Every billing table has a primary key.".to_string(),
+///                 },
+///                 false => CheckOutcome {
+///                     severity: Severity::Warning,
+///                     message: format!("Billing tables without a primary key: {}", tables.join(", ")),
+///                 },
+///             })
+///         })
+///     }
+///
+///     fn recommendation(&self) -> (&str, Vec<&str>) {
+///         ("Add primary keys", vec!["Logical replication needs a primary key on every table."])
+///     }
+/// }
+///
+/// # async fn example() -> Result<(), PgExtrasError> {
+/// let client = PgExtras::connect().await?.with_check(BillingPrimaryKeys);
+/// let checks = client.diagnose().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait DiagnoseCheck: Send + Sync {
+    /// Name shown in the report, e.g. `billing_primary_keys`.
+    fn name(&self) -> &str;
+    /// Runs the check, queries should honour `client.query_timeouts` for the check name.
+    fn run<'a>(&'a self, client: &'a PgExtras) -> CheckFuture<'a>;
+    /// Header and details shown when the check does not pass.
+    fn recommendation(&self) -> (&str, Vec<&str>);
+}
+
+/// User checks run after the built-in ones, in the order they were registered.
+#[derive(Clone, Default)]
+pub struct CheckRegistry {
+    checks: Vec<Arc<dyn DiagnoseCheck>>,
+}
+
+impl CheckRegistry {
+    pub fn register(&mut self, check: impl DiagnoseCheck + 'static) {
+        self.checks.push(Arc::new(check));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.checks.iter().map(|check| check.name()).collect()
+    }
+
+    async fn run(&self, client: &PgExtras) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+        for check in &self.checks {
+            let outcome = check.run(client).await.unwrap_or_else(CheckOutcome::failed);
+            let (header, details) = check.recommendation();
+            results.push(CheckResult {
+                severity: outcome.severity,
+                message: outcome.message,
+                check: Check::Custom {
+                    name: check.name().to_string(),
+                    header: header.to_string(),
+                    details: details.iter().map(|detail| detail.to_string()).collect(),
+                },
+                threshold: None,
+            });
+        }
+        results
+    }
+}
+
+impl fmt::Debug for CheckRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Where the checks read their rows from, a live client or an offline snapshot.
+pub(crate) trait DiagnoseSource {
+    fn capabilities(&self) -> &ServerCapabilities;
+    async fn rows<T: Query>(
+        &self,
+        params: HashMap<QueryParam, String>,
+    ) -> Result<Vec<T>, PgExtrasError>;
+}
+
+impl DiagnoseSource for PgExtras {
+    fn capabilities(&self) -> &ServerCapabilities {
+        self.capabilities()
+    }
+
+    async fn rows<T: Query>(
+        &self,
+        params: HashMap<QueryParam, String>,
+    ) -> Result<Vec<T>, PgExtrasError> {
+        self.get_rows(Some(params)).await
+    }
+}
+
+/// Runs the built-in checks with `config`, then the checks registered on the client.
+pub async fn run_diagnose(
+    client: &PgExtras,
+    config: &DiagnoseConfig,
+) -> Result<Vec<CheckResult>, PgExtrasError> {
+    let mut results = run_checks(client, config).await?;
+    results.extend(client.checks().run(client).await);
+    Ok(results)
+}
+
+/// Fails only on an invalid `config`, checks returning an error are reported as critical.
+pub(crate) async fn run_checks<S: DiagnoseSource>(
+    source: &S,
+    config: &DiagnoseConfig,
+) -> Result<Vec<CheckResult>, PgExtrasError> {
+    config.validate()?;
+
     let mut checks = vec![
         Check::TableCacheHit,
         Check::IndexCacheHit,
@@ -68,223 +349,305 @@ pub async fn run_diagnose(pool: &Pool<Postgres>) -> Result<Vec<CheckResult>, PgE
         Check::DuplicateIndexes,
     ];
 
-    let extensions_data = extensions(pool).await?;
+    let capabilities = source.capabilities();
 
-    if extension_enabled(&extensions_data, "sslinfo") {
+    if capabilities.has_extension("sslinfo") {
         checks.push(Check::SslUsed);
     }
 
-    if extension_enabled(&extensions_data, "pg_stat_statements") {
+    if capabilities.has_extension("pg_stat_statements") {
         checks.push(Check::Outliers);
     }
 
     let mut results = Vec::new();
     for check in checks {
-        results.push(run_check(check, pool).await?);
+        let result = run_check(check.clone(), source, config).await;
+        results.push(result.unwrap_or_else(|e| {
+            let outcome = CheckOutcome::failed(e);
+            CheckResult {
+                severity: outcome.severity,
+                message: outcome.message,
+                threshold: check.threshold(config),
+                check,
+            }
+        }));
     }
 
     Ok(results)
 }
 
-fn extension_enabled(extensions_data: &[Extensions], extension_name: &str) -> bool {
-    extensions_data
-        .iter()
-        .any(|e| e.name == extension_name && !e.installed_version.is_empty())
-}
-
-async fn run_check(check: Check, pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    match check {
-        Check::TableCacheHit => check_table_cache_hit(pool).await,
-        Check::IndexCacheHit => check_index_cache_hit(pool).await,
-        Check::UnusedIndexes => check_unused_index(pool).await,
-        Check::NullIndexes => check_null_index(pool).await,
-        Check::Bloat => check_bloat(pool).await,
-        Check::DuplicateIndexes => check_duplicate_indexes(pool).await,
-        Check::SslUsed => detect_ssl_used(pool).await,
-        Check::Outliers => check_outliers(pool).await,
+async fn run_check<S: DiagnoseSource>(
+    check: Check,
+    client: &S,
+    config: &DiagnoseConfig,
+) -> Result<CheckResult, PgExtrasError> {
+    match (check.threshold(config), check) {
+        (Some(threshold), Check::TableCacheHit) => check_table_cache_hit(client, threshold).await,
+        (Some(threshold), Check::IndexCacheHit) => check_index_cache_hit(client, threshold).await,
+        (Some(threshold), Check::UnusedIndexes) => check_unused_index(client, threshold).await,
+        (Some(threshold), Check::NullIndexes) => check_null_index(client, config, threshold).await,
+        (Some(threshold), Check::Bloat) => check_bloat(client, threshold).await,
+        (Some(threshold), Check::Outliers) => check_outliers(client, threshold).await,
+        (_, Check::DuplicateIndexes) => check_duplicate_indexes(client).await,
+        (_, Check::SslUsed) => detect_ssl_used(client).await,
+        (_, check) => unreachable!("{} is not a built-in check with a threshold", check),
     }
 }
 
-async fn check_table_cache_hit(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let min_expected = BigDecimal::try_from(TABLE_CACHE_HIT_MIN).unwrap();
-    let cache_hit = cache_hit(None, pool).await?;
+async fn check_table_cache_hit(
+    client: &impl DiagnoseSource,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let cache_hit = client.rows::<CacheHit>(HashMap::new()).await?;
     let table_cache_hit = cache_hit.iter().find(|item| item.name == "table hit rate");
 
     let Some(table_hit_rate) = table_cache_hit else {
         return Ok(CheckResult {
-            ok: false,
+            severity: Severity::Info,
             message: "Table cache hit rate not found".to_string(),
             check: Check::TableCacheHit,
+            threshold: Some(threshold),
         });
     };
 
-    let ok = table_hit_rate.ratio >= min_expected;
+    let severity = threshold.severity_below(table_hit_rate.ratio);
     let message = format!(
         "Table cache hit rate is {}: {:.4}",
-        if ok { "correct" } else { "too low" },
+        if severity == Severity::Ok {
+            "correct"
+        } else {
+            "too low"
+        },
         table_hit_rate.ratio
     );
 
     Ok(CheckResult {
-        ok,
+        severity,
         message,
         check: Check::TableCacheHit,
+        threshold: Some(threshold),
     })
 }
 
-async fn check_index_cache_hit(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let min_expected = BigDecimal::try_from(INDEX_CACHE_HIT_MIN).unwrap();
-    let cache_hit = cache_hit(None, pool).await?;
+async fn check_index_cache_hit(
+    client: &impl DiagnoseSource,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let cache_hit = client.rows::<CacheHit>(HashMap::new()).await?;
     let index_cache_hit = cache_hit.iter().find(|item| item.name == "index hit rate");
 
     let Some(index_hit_rate) = index_cache_hit else {
         return Ok(CheckResult {
-            ok: false,
+            severity: Severity::Info,
             message: "Index cache hit rate not found".to_string(),
             check: Check::IndexCacheHit,
+            threshold: Some(threshold),
         });
     };
 
-    let ok = index_hit_rate.ratio >= min_expected;
+    let severity = threshold.severity_below(index_hit_rate.ratio);
     let message = format!(
         "Index cache hit rate is {}: {:.4}",
-        if ok { "correct" } else { "too low" },
+        if severity == Severity::Ok {
+            "correct"
+        } else {
+            "too low"
+        },
         index_hit_rate.ratio
     );
 
     Ok(CheckResult {
-        ok,
+        severity,
         message,
         check: Check::IndexCacheHit,
+        threshold: Some(threshold),
     })
 }
 
-async fn detect_ssl_used(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let ssl_results = ssl_used(pool).await?;
+async fn detect_ssl_used(client: &impl DiagnoseSource) -> Result<CheckResult, PgExtrasError> {
+    let ssl_results = client.rows::<SslUsed>(HashMap::new()).await?;
     let Some(ssl_conn) = ssl_results.first() else {
         return Ok(CheckResult {
-            ok: false,
+            severity: Severity::Info,
             message: "Unable to get connection information.".to_string(),
             check: Check::SslUsed,
+            threshold: None,
         });
     };
 
-    let message = if ssl_conn.ssl_used {
-        "Database client is using a secure SSL connection."
+    let (severity, message) = if ssl_conn.ssl_used {
+        (
+            Severity::Ok,
+            "Database client is using a secure SSL connection.",
+        )
     } else {
-        "Database client is using an unencrypted connection."
+        (
+            Severity::Critical,
+            "Database client is using an unencrypted connection.",
+        )
     };
 
     Ok(CheckResult {
-        ok: ssl_conn.ssl_used,
+        severity,
         message: message.to_string(),
         check: Check::SslUsed,
+        threshold: None,
     })
 }
 
-async fn check_unused_index(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let indexes = unused_indexes(None, pool)
+async fn check_unused_index(
+    client: &impl DiagnoseSource,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let indexes = client
+        .rows::<UnusedIndexes>(HashMap::new())
         .await?
         .into_iter()
-        .filter(|i| to_bytes(&i.index_size).unwrap_or(0) >= UNUSED_INDEXES_MIN_SIZE_BYTES)
+        .filter(|i| i.index_size as f64 >= threshold.warning)
         .collect::<Vec<_>>();
 
     if indexes.is_empty() {
         return Ok(CheckResult {
-            ok: true,
+            severity: Severity::Ok,
             message: "No unused indexes detected.".to_string(),
             check: Check::UnusedIndexes,
+            threshold: Some(threshold),
         });
     }
 
+    let severity = indexes
+        .iter()
+        .map(|i| threshold.severity_above(i.index_size as f64))
+        .max()
+        .unwrap_or(Severity::Warning);
     let print_indexes = indexes
         .iter()
-        .map(|i| format!("'{}' on '{}' size {}", i.index, i.table, i.index_size))
+        .map(|i| {
+            format!(
+                "'{}' on '{}' size {}",
+                i.index,
+                i.table,
+                format_bytes(i.index_size)
+            )
+        })
         .collect::<Vec<_>>()
         .join(",\n");
 
     Ok(CheckResult {
-        ok: false,
+        severity,
         message: format!("Unused indexes detected:\n{}", print_indexes),
         check: Check::UnusedIndexes,
+        threshold: Some(threshold),
     })
 }
 
-async fn check_null_index(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let indexes = null_indexes(Some(NULL_INDEXES_MIN_SIZE_MB.to_string()), pool)
+async fn check_null_index(
+    client: &impl DiagnoseSource,
+    config: &DiagnoseConfig,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let indexes = client
+        .rows::<NullIndexes>(HashMap::from([(
+            QueryParam::MinRelationSizeMb,
+            config.null_indexes_min_size_mb.to_string(),
+        )]))
         .await?
         .into_iter()
-        .filter(|i| {
-            i.null_frac
-                .trim_end_matches('%')
-                .parse::<f64>()
-                .unwrap_or(0.0)
-                >= NULL_MIN_NULL_FRAC_PERCENT
-        })
+        .filter(|i| i.null_frac >= threshold.warning)
         .collect::<Vec<_>>();
 
     if indexes.is_empty() {
         return Ok(CheckResult {
-            ok: true,
+            severity: Severity::Ok,
             message: "No null indexes detected.".to_string(),
             check: Check::NullIndexes,
+            threshold: Some(threshold),
         });
     }
 
+    let severity = indexes
+        .iter()
+        .map(|i| threshold.severity_above(i.null_frac))
+        .max()
+        .unwrap_or(Severity::Warning);
     let print_indexes = indexes
         .iter()
         .map(|i| {
             format!(
                 "'{}' size {} null values fraction {}",
-                i.index, i.index_size, i.null_frac
+                i.index,
+                format_bytes(i.index_size),
+                format_percent(i.null_frac)
             )
         })
         .collect::<Vec<_>>()
         .join(",\n");
 
     Ok(CheckResult {
-        ok: false,
+        severity,
         message: format!("Null indexes detected:\n{}", print_indexes),
         check: Check::NullIndexes,
+        threshold: Some(threshold),
     })
 }
 
-async fn check_bloat(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let bloat_data = bloat(pool)
+async fn check_bloat(
+    client: &impl DiagnoseSource,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let bloat_data = client
+        .rows::<Bloat>(HashMap::new())
         .await?
         .into_iter()
-        .filter(|b| b.bloat >= BigDecimal::try_from(BLOAT_MIN_VALUE).unwrap())
+        .filter(|b| b.bloat >= threshold.warning)
         .collect::<Vec<_>>();
 
     if bloat_data.is_empty() {
         return Ok(CheckResult {
-            ok: true,
+            severity: Severity::Ok,
             message: "No bloat detected.".to_string(),
             check: Check::Bloat,
+            threshold: Some(threshold),
         });
     }
 
+    let severity = bloat_data
+        .iter()
+        .map(|b| threshold.severity_above(b.bloat))
+        .max()
+        .unwrap_or(Severity::Warning);
     let print_bloat = bloat_data
         .iter()
-        .map(|b| format!("'{}' bloat {} waste {}", b.object_name, b.bloat, b.waste))
+        .map(|b| {
+            format!(
+                "'{}' bloat {:.1} waste {}",
+                b.object_name,
+                b.bloat,
+                format_bytes(b.waste)
+            )
+        })
         .collect::<Vec<_>>()
         .join(",\n");
 
     Ok(CheckResult {
-        ok: false,
+        severity,
         message: format!("Bloat detected:\n{}", print_bloat),
         check: Check::Bloat,
+        threshold: Some(threshold),
     })
 }
 
-async fn check_duplicate_indexes(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let indexes = duplicate_indexes(pool).await?;
+async fn check_duplicate_indexes(
+    client: &impl DiagnoseSource,
+) -> Result<CheckResult, PgExtrasError> {
+    let indexes = client.rows::<DuplicateIndexes>(HashMap::new()).await?;
 
     if indexes.is_empty() {
         return Ok(CheckResult {
-            ok: true,
+            severity: Severity::Ok,
             message: "No duplicate indexes detected.".to_string(),
             check: Check::DuplicateIndexes,
+            threshold: None,
         });
     }
 
@@ -293,36 +656,47 @@ async fn check_duplicate_indexes(pool: &Pool<Postgres>) -> Result<CheckResult, P
         .map(|i| {
             format!(
                 "'{}' of size {} is identical to '{}'",
-                i.idx1, i.size, i.idx2
+                i.idx1,
+                format_bytes(i.size),
+                i.idx2
             )
         })
         .collect::<Vec<_>>()
         .join(",\n");
 
     Ok(CheckResult {
-        ok: false,
+        severity: Severity::Warning,
         message: format!("Duplicate indexes detected:\n{}", print_indexes),
         check: Check::DuplicateIndexes,
+        threshold: None,
     })
 }
 
-async fn check_outliers(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasError> {
-    let queries = outliers(pool)
+async fn check_outliers(
+    client: &impl DiagnoseSource,
+    threshold: Threshold,
+) -> Result<CheckResult, PgExtrasError> {
+    let queries = client
+        .rows::<Outliers>(HashMap::new())
         .await?
         .into_iter()
-        .filter(|q| {
-            q.prop_exec_time.replace("%", "").parse::<f64>().unwrap() >= OUTLIERS_MIN_EXEC_RATIO
-        })
+        .filter(|q| q.prop_exec_time >= threshold.warning)
         .collect::<Vec<_>>();
 
     if queries.is_empty() {
         return Ok(CheckResult {
-            ok: true,
+            severity: Severity::Ok,
             message: "No queries using significant execution ratio detected.".to_string(),
             check: Check::Outliers,
+            threshold: Some(threshold),
         });
     }
 
+    let severity = queries
+        .iter()
+        .map(|q| threshold.severity_above(q.prop_exec_time))
+        .max()
+        .unwrap_or(Severity::Warning);
     let print_queries = queries
         .iter()
         .map(|q| {
@@ -330,18 +704,54 @@ async fn check_outliers(pool: &Pool<Postgres>) -> Result<CheckResult, PgExtrasEr
                 "'{}...' called {} times, using {} of total exec time.",
                 q.query.chars().take(30).collect::<String>(),
                 q.ncalls,
-                q.prop_exec_time
+                format_percent(q.prop_exec_time)
             )
         })
         .collect::<Vec<_>>()
         .join(",\n");
 
     Ok(CheckResult {
-        ok: false,
+        severity,
         message: format!(
             "Queries using significant execution ratio detected:\n{}",
             print_queries
         ),
         check: Check::Outliers,
+        threshold: Some(threshold),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(check: Check, severity: Severity) -> CheckResult {
+        CheckResult {
+            severity,
+            message: String::new(),
+            check,
+            threshold: None,
+        }
+    }
+
+    #[test]
+    fn weights_health_score_by_check_and_severity() {
+        assert_eq!(health_score(&[]), 100);
+        assert_eq!(
+            health_score(&[
+                result(Check::SslUsed, Severity::Ok),
+                result(Check::DuplicateIndexes, Severity::Info),
+            ]),
+            100
+        );
+        // ssl 3 of 3, table cache hit 1.5 of 3, bloat 0 of 2
+        assert_eq!(
+            health_score(&[
+                result(Check::SslUsed, Severity::Ok),
+                result(Check::TableCacheHit, Severity::Warning),
+                result(Check::Bloat, Severity::Critical),
+            ]),
+            56
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{QueryKind, QueryOutput, Snapshot};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Rows added, removed and changed between two snapshots, for queries with key columns.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotDiff {
+    pub queries: Vec<QueryDiff>,
+    /// Queries missing from one of the snapshots, with the reason.
+    pub skipped: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryDiff {
+    pub query: String,
+    pub key_columns: Vec<String>,
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<RowChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowChange {
+    /// Values of the key columns, joined with a dot, e.g. `public.users`.
+    pub key: String,
+    pub changes: Vec<ValueChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueChange {
+    pub column: String,
+    pub before: Value,
+    pub after: Value,
+    /// `after - before` when both values are numbers.
+    pub delta: Option<Value>,
+}
+
+impl Snapshot {
+    /// Matches the rows of both snapshots by the query key columns.
+    pub fn diff(&self, after: &Snapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        for kind in QueryKind::ALL {
+            if kind.key_columns().is_empty() {
+                continue;
+            }
+
+            match (kind.from_snapshot(self), kind.from_snapshot(after)) {
+                (Ok(before_output), Ok(after_output)) => {
+                    let query_diff = diff_outputs(kind, &before_output, &after_output);
+                    if !query_diff.is_empty() {
+                        diff.queries.push(query_diff);
+                    }
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    diff.skipped.insert(kind.name().to_string(), e.to_string());
+                }
+            }
+        }
+        diff
+    }
+}
+
+impl QueryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    /// One row per added or removed row and per changed value, for `write_output`.
+    pub fn to_output(&self) -> QueryOutput {
+        let headers = [
+            "query", "change", "key", "column", "before", "after", "delta",
+        ];
+        let mut rows = vec![];
+        for query in &self.queries {
+            let key_columns = query
+                .key_columns
+                .iter()
+                .map(|column| column.as_str())
+                .collect::<Vec<_>>();
+            for row in &query.added {
+                rows.push(json!({
+                    "query": query.query,
+                    "change": "added",
+                    "key": row_key(row, &key_columns),
+                    "after": row,
+                }));
+            }
+            for row in &query.removed {
+                rows.push(json!({
+                    "query": query.query,
+                    "change": "removed",
+                    "key": row_key(row, &key_columns),
+                    "before": row,
+                }));
+            }
+            for row in &query.changed {
+                for change in &row.changes {
+                    rows.push(json!({
+                        "query": query.query,
+                        "change": "changed",
+                        "key": row.key,
+                        "column": change.column,
+                        "before": change.before,
+                        "after": change.after,
+                        "delta": change.delta,
+                    }));
+                }
+            }
+        }
+
+        let cells = rows
+            .iter()
+            .map(|row| {
+                headers
+                    .iter()
+                    .map(|header| match *header {
+                        "key" => key_cell(&cell(&row[*header])),
+                        header => cell(&row[header]),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        QueryOutput {
+            description: "Snapshot diff".to_string(),
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows,
+            cells,
+        }
+    }
+}
+
+fn diff_outputs(kind: &QueryKind, before: &QueryOutput, after: &QueryOutput) -> QueryDiff {
+    let key_columns = kind.key_columns();
+    let before_rows = index_rows(&before.rows, &key_columns);
+    let after_rows = index_rows(&after.rows, &key_columns);
+
+    let mut diff = QueryDiff {
+        query: kind.name().to_string(),
+        key_columns: key_columns
+            .iter()
+            .map(|column| column.to_string())
+            .collect(),
+        added: vec![],
+        removed: vec![],
+        changed: vec![],
+    };
+
+    for row in &after.rows {
+        let key = row_key(row, &key_columns);
+        match before_rows.get(&key) {
+            None => diff.added.push(row.clone()),
+            Some(before_row) if before_row != &row => {
+                let changes = after
+                    .headers
+                    .iter()
+                    .filter(|column| !key_columns.contains(&column.as_str()))
+                    .filter_map(|column| value_change(column, &before_row[column], &row[column]))
+                    .collect::<Vec<_>>();
+                if !changes.is_empty() {
+                    diff.changed.push(RowChange { key, changes });
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    for row in &before.rows {
+        if !after_rows.contains_key(&row_key(row, &key_columns)) {
+            diff.removed.push(row.clone());
+        }
+    }
+
+    diff
+}
+
+/// Rows by key, the first row wins when a key is repeated.
+pub(crate) fn index_rows<'a>(
+    rows: &'a [Value],
+    key_columns: &[&str],
+) -> HashMap<String, &'a Value> {
+    let mut index = HashMap::new();
+    for row in rows {
+        index.entry(row_key(row, key_columns)).or_insert(row);
+    }
+    index
+}
+
+pub(crate) fn row_key(row: &Value, key_columns: &[&str]) -> String {
+    key_columns
+        .iter()
+        .map(|column| cell(&row[*column]))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn value_change(column: &str, before: &Value, after: &Value) -> Option<ValueChange> {
+    if before == after {
+        return None;
+    }
+
+    let delta = match (before, after) {
+        (Value::Number(before), Value::Number(after)) => match (before.as_i64(), after.as_i64()) {
+            (Some(before), Some(after)) => Some(json!(after - before)),
+            _ => Some(json!(after.as_f64()? - before.as_f64()?)),
+        },
+        _ => None,
+    };
+
+    Some(ValueChange {
+        column: column.to_string(),
+        before: before.clone(),
+        after: after.clone(),
+        delta,
+    })
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Keys on a single line, long ones like query texts are shortened for display.
+fn key_cell(key: &str) -> String {
+    const MAX_KEY_LEN: usize = 60;
+
+    let key = key.split_whitespace().collect::<Vec<_>>().join(" ");
+    if key.chars().count() <= MAX_KEY_LEN {
+        return key;
+    }
+    format!("{}...", key.chars().take(MAX_KEY_LEN).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{SnapshotQuery, SNAPSHOT_VERSION};
+    use crate::ServerCapabilities;
+    use std::time::SystemTime;
+
+    fn snapshot(table_sizes: Vec<Value>, settings: Vec<Value>) -> Snapshot {
+        let query = |rows| SnapshotQuery {
+            params: BTreeMap::new(),
+            rows,
+        };
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: SystemTime::UNIX_EPOCH,
+            server_version: "16.4".to_string(),
+            pg_stat_statements_version: None,
+            capabilities: ServerCapabilities::default(),
+            db_settings: vec![],
+            queries: BTreeMap::from([
+                ("table_size".to_string(), query(table_sizes)),
+                ("db_settings".to_string(), query(settings)),
+            ]),
+            skipped: BTreeMap::new(),
+        }
+    }
+
+    fn table(name: &str, size: i64) -> Value {
+        json!({ "name": name, "size": size, "schema": "public" })
+    }
+
+    fn setting(name: &str, setting: &str) -> Value {
+        json!({ "name": name, "setting": setting, "unit": "", "short_desc": "" })
+    }
+
+    #[test]
+    fn matches_rows_by_key() {
+        let before = snapshot(
+            vec![table("users", 8192), table("orders", 100)],
+            vec![setting("work_mem", "4096")],
+        );
+        let after = snapshot(
+            vec![table("users", 16384), table("events", 50)],
+            vec![setting("work_mem", "4096")],
+        );
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.queries.len(), 1);
+        let tables = &diff.queries[0];
+        assert_eq!(tables.query, "table_size");
+        assert_eq!(tables.added, vec![table("events", 50)]);
+        assert_eq!(tables.removed, vec![table("orders", 100)]);
+        assert_eq!(tables.changed.len(), 1);
+        assert_eq!(tables.changed[0].key, "public.users");
+        assert_eq!(tables.changed[0].changes[0].column, "size");
+        assert_eq!(tables.changed[0].changes[0].delta, Some(json!(8192)));
+
+        // queries missing from the snapshots can't be compared
+        assert!(diff.skipped.contains_key("outliers"));
+    }
+
+    #[test]
+    fn flattens_changes_into_rows() {
+        let before = snapshot(vec![], vec![setting("work_mem", "4096")]);
+        let after = snapshot(vec![], vec![setting("work_mem", "8192")]);
+
+        let output = before.diff(&after).to_output();
+        assert_eq!(output.rows.len(), 1);
+        assert_eq!(
+            output.cells[0],
+            vec![
+                "db_settings",
+                "changed",
+                "work_mem",
+                "setting",
+                "4096",
+                "8192",
+                ""
+            ]
+        );
+    }
+}
@@ -1,14 +1,38 @@
 use std::{
     collections::HashMap,
-    time::Duration,
+    sync::Arc,
     {env, fmt},
 };
+pub mod backends;
+pub mod capabilities;
+pub mod client;
+pub mod config;
+pub mod connection;
+pub mod custom_queries;
 pub mod diagnose;
+pub mod diff;
+pub mod output;
 pub mod queries;
-
+pub mod rates;
+pub mod registry;
+pub mod reset;
+pub mod snapshot;
+pub mod watch;
+
+#[cfg(feature = "tui")]
+pub mod tui;
 #[cfg(feature = "web")]
 pub mod web;
 
+pub use backends::{Backend, BackendFilter, BackendSignal, BackendTargets, Protection};
+pub use capabilities::{RolePrivileges, ServerCapabilities};
+pub use client::{PgExtras, Timeouts};
+pub use config::{Config, Profile};
+pub use connection::ConnectionBuilder;
+pub use custom_queries::{Comparison, CustomQuery, RowCheck};
+pub use diagnose::config::DiagnoseConfig;
+pub use diff::SnapshotDiff;
+pub use output::{write_output, OutputFormat};
 pub use queries::{
     all_locks::AllLocks,
     bloat::Bloat,
@@ -33,7 +57,7 @@ pub use queries::{
     outliers::Outliers,
     records_rank::RecordsRank,
     seq_scans::SeqScans,
-    shared::{get_default_schema, Query},
+    shared::{get_default_schema, ParamValue, Query, QueryParam},
     ssl_used::SslUsed,
     table_cache_hit::TableCacheHit,
     table_index_scans::TableIndexScans,
@@ -45,268 +69,455 @@ pub use queries::{
     unused_indexes::UnusedIndexes,
     vacuum_stats::VacuumStats,
 };
+pub use rates::{RateQuery, Rates};
+pub use registry::{QueryKind, QueryOutput};
+pub use reset::{SharedStats, StatsReset};
 use semver::Version;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres, Row};
+pub use snapshot::Snapshot;
+use sqlx::postgres::{PgArguments, PgConnection, PgRow};
+use sqlx::{Acquire, Pool, Postgres};
+pub use watch::{RowStatus, Watch, WatchTick};
 
 #[macro_use]
 extern crate prettytable;
-use prettytable::{Cell, Row as TableRow, Table};
 
 /// Renders a table to stdout for any type that implements the Query trait.
 pub fn render_table<T: Query>(items: Vec<T>) {
-    let mut table = Table::new();
-    table.add_row(T::headers());
-
-    let columns_count = T::headers().len();
+    render_output(&QueryOutput::new(&items));
+}
 
-    for item in items {
-        table.add_row(item.to_row());
-    }
-    table.set_titles(TableRow::new(vec![
-        Cell::new(T::description().as_str()).style_spec(format!("H{}", columns_count).as_str())
-    ]));
-    table.printstd();
+/// Renders a table to stdout for the type-erased rows of a query.
+pub fn render_output(output: &QueryOutput) {
+    output::output_table(output).printstd();
 }
 
 /// Returns table and index bloat in your database ordered by most wasteful.
-pub async fn bloat(pool: &Pool<Postgres>) -> Result<Vec<Bloat>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::bloat`")]
+pub async fn bloat<'a, A>(conn: A) -> Result<Vec<Bloat>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Lists queries that are blocking other queries.
-pub async fn blocking(
-    limit: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<Blocking>, PgExtrasError> {
-    get_rows(Some(limit_params(limit)), pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::blocking`")]
+pub async fn blocking<'a, A>(limit: Option<String>, conn: A) -> Result<Vec<Blocking>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(Some(limit_params(limit)), None, &Timeouts::default(), conn).await
 }
 
 /// Creates a new connection pool to PostgreSQL.
 ///
-/// Uses either PG_EXTRAS_DATABASE_URL or DATABASE_URL environment variable.
+/// Uses PG_EXTRAS_DATABASE_URL or DATABASE_URL environment variable, falling back to
+/// the libpq `PG*` variables. See `ConnectionBuilder` for the full resolution order.
 pub async fn pg_pool() -> Result<Pool<Postgres>, PgExtrasError> {
-    match PgPoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(Duration::from_secs(10))
-        .connect(db_url()?.as_str())
-        .await
-    {
-        Ok(pool) => Ok(pool),
-        Err(e) => Err(PgExtrasError::DbConnectionError(format!("{}", e))),
-    }
+    ConnectionBuilder::new().pool().await
 }
 
 /// Returns statistics about query calls in the database.
-pub async fn calls(
-    limit: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<Calls>, PgExtrasError> {
-    get_rows(Some(limit_params(limit)), pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::calls`")]
+pub async fn calls<'a, A>(limit: Option<String>, conn: A) -> Result<Vec<Calls>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(Some(limit_params(limit)), None, &Timeouts::default(), conn).await
 }
 
 /// Lists all installed PostgreSQL extensions.
-pub async fn extensions(pool: &Pool<Postgres>) -> Result<Vec<Extensions>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::extensions`")]
+pub async fn extensions<'a, A>(conn: A) -> Result<Vec<Extensions>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows cache hit rates for tables.
-pub async fn table_cache_hit(pool: &Pool<Postgres>) -> Result<Vec<TableCacheHit>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::table_cache_hit`"
+)]
+pub async fn table_cache_hit<'a, A>(conn: A) -> Result<Vec<TableCacheHit>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Lists all tables in the database with their basic information.
-pub async fn tables(
-    schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<Tables>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::tables`")]
+pub async fn tables<'a, A>(schema: Option<String>, conn: A) -> Result<Vec<Tables>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows index cache hit rates.
-pub async fn index_cache_hit(
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::index_cache_hit`"
+)]
+pub async fn index_cache_hit<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<IndexCacheHit>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<IndexCacheHit>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Lists all indexes in the database.
-pub async fn indexes(pool: &Pool<Postgres>) -> Result<Vec<Indexes>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::indexes`")]
+pub async fn indexes<'a, A>(conn: A) -> Result<Vec<Indexes>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows the size of all indexes, ordered by size.
-pub async fn index_size(pool: &Pool<Postgres>) -> Result<Vec<IndexSize>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::index_size`")]
+pub async fn index_size<'a, A>(conn: A) -> Result<Vec<IndexSize>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows statistics about index usage.
-pub async fn index_usage(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::index_usage`")]
+pub async fn index_usage<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<IndexUsage>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<IndexUsage>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows statistics about index scans.
-pub async fn index_scans(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::index_scans`")]
+pub async fn index_scans<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<IndexScans>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<IndexScans>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows indexes that contain mostly NULL values.
-pub async fn null_indexes(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::null_indexes`")]
+pub async fn null_indexes<'a, A>(
     min_relation_size_mb: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<NullIndexes>, PgExtrasError> {
-    let min_relation_size_mb = min_relation_size_mb.unwrap_or("10".to_string());
-
-    let params: HashMap<String, String> = [(
-        "min_relation_size_mb".to_string(),
-        min_relation_size_mb.to_string(),
-    )]
-    .iter()
-    .cloned()
-    .collect();
-    get_rows(Some(params), pool).await
+    conn: A,
+) -> Result<Vec<NullIndexes>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(param_args(
+            QueryParam::MinRelationSizeMb,
+            min_relation_size_mb,
+        )),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows information about locks in the database.
-pub async fn locks(pool: &Pool<Postgres>) -> Result<Vec<Locks>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::locks`")]
+pub async fn locks<'a, A>(conn: A) -> Result<Vec<Locks>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows detailed information about all locks in the database.
-pub async fn all_locks(pool: &Pool<Postgres>) -> Result<Vec<AllLocks>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::all_locks`")]
+pub async fn all_locks<'a, A>(conn: A) -> Result<Vec<AllLocks>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Lists currently running queries that have been running for a long time.
-pub async fn long_running_queries(
-    pool: &Pool<Postgres>,
-) -> Result<Vec<LongRunningQueries>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::long_running_queries`"
+)]
+pub async fn long_running_queries<'a, A>(conn: A) -> Result<Vec<LongRunningQueries>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Generates a Mandelbrot set as a test query.
-pub async fn mandelbrot(pool: &Pool<Postgres>) -> Result<Vec<Mandelbrot>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::mandelbrot`")]
+pub async fn mandelbrot<'a, A>(conn: A) -> Result<Vec<Mandelbrot>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows queries with the longest execution time in aggregate.
-pub async fn outliers(pool: &Pool<Postgres>) -> Result<Vec<Outliers>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::outliers`")]
+pub async fn outliers<'a, A>(conn: A) -> Result<Vec<Outliers>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows estimated number of rows in each table, ordered by estimated count.
-pub async fn records_rank(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::records_rank`")]
+pub async fn records_rank<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<RecordsRank>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<RecordsRank>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows statistics about sequential scans performed on tables.
-pub async fn seq_scans(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::seq_scans`")]
+pub async fn seq_scans<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<SeqScans>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<SeqScans>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows statistics about index scans performed on tables.
-pub async fn table_index_scans(
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::table_index_scans`"
+)]
+pub async fn table_index_scans<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<TableIndexScans>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<TableIndexScans>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows total size of all indexes for each table.
-pub async fn table_indexes_size(
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::table_indexes_size`"
+)]
+pub async fn table_indexes_size<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<TableIndexesSize>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<TableIndexesSize>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows disk space used by each table, excluding indexes.
-pub async fn table_size(pool: &Pool<Postgres>) -> Result<Vec<TableSize>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::table_size`")]
+pub async fn table_size<'a, A>(conn: A) -> Result<Vec<TableSize>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows total size of all indexes in the database.
-pub async fn total_index_size(pool: &Pool<Postgres>) -> Result<Vec<TotalIndexSize>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::total_index_size`"
+)]
+pub async fn total_index_size<'a, A>(conn: A) -> Result<Vec<TotalIndexSize>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows total disk space used by tables and indexes.
-pub async fn total_table_size(pool: &Pool<Postgres>) -> Result<Vec<TotalTableSize>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::total_table_size`"
+)]
+pub async fn total_table_size<'a, A>(conn: A) -> Result<Vec<TotalTableSize>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Lists indexes that haven't been used or are rarely used.
-pub async fn unused_indexes(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::unused_indexes`")]
+pub async fn unused_indexes<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<UnusedIndexes>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<UnusedIndexes>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows indexes that have identical definitions but different names.
-pub async fn duplicate_indexes(
-    pool: &Pool<Postgres>,
-) -> Result<Vec<DuplicateIndexes>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::duplicate_indexes`"
+)]
+pub async fn duplicate_indexes<'a, A>(conn: A) -> Result<Vec<DuplicateIndexes>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows statistics about VACUUM and ANALYZE operations.
-pub async fn vacuum_stats(pool: &Pool<Postgres>) -> Result<Vec<VacuumStats>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::vacuum_stats`")]
+pub async fn vacuum_stats<'a, A>(conn: A) -> Result<Vec<VacuumStats>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows statistics about shared buffer cache usage.
-pub async fn buffercache_stats(
-    pool: &Pool<Postgres>,
-) -> Result<Vec<BuffercacheStats>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::buffercache_stats`"
+)]
+pub async fn buffercache_stats<'a, A>(conn: A) -> Result<Vec<BuffercacheStats>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows distribution of buffer cache usage by database objects.
-pub async fn buffercache_usage(
-    pool: &Pool<Postgres>,
-) -> Result<Vec<BuffercacheUsage>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(
+    note = "probes the server capabilities on every call, use `PgExtras::buffercache_usage`"
+)]
+pub async fn buffercache_usage<'a, A>(conn: A) -> Result<Vec<BuffercacheUsage>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows whether SSL is being used for current connections.
-pub async fn ssl_used(pool: &Pool<Postgres>) -> Result<Vec<SslUsed>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::ssl_used`")]
+pub async fn ssl_used<'a, A>(conn: A) -> Result<Vec<SslUsed>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows information about current database connections and their states.
-pub async fn connections(pool: &Pool<Postgres>) -> Result<Vec<Connections>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::connections`")]
+pub async fn connections<'a, A>(conn: A) -> Result<Vec<Connections>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Shows cache hit rates for both tables and indexes.
-pub async fn cache_hit(
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::cache_hit`")]
+pub async fn cache_hit<'a, A>(
     schema: Option<String>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<CacheHit>, PgExtrasError> {
-    get_rows(Some(schema_params(schema)), pool).await
+    conn: A,
+) -> Result<Vec<CacheHit>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(
+        Some(schema_params(schema)),
+        None,
+        &Timeouts::default(),
+        conn,
+    )
+    .await
 }
 
 /// Shows current values of important PostgreSQL settings.
-pub async fn db_settings(pool: &Pool<Postgres>) -> Result<Vec<DbSettings>, PgExtrasError> {
-    get_rows(None, pool).await
+#[deprecated(note = "probes the server capabilities on every call, use `PgExtras::db_settings`")]
+pub async fn db_settings<'a, A>(conn: A) -> Result<Vec<DbSettings>, PgExtrasError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    get_rows(None, None, &Timeouts::default(), conn).await
 }
 
 /// Runs a comprehensive set of diagnostic checks on the database.
 pub async fn diagnose(pool: &Pool<Postgres>) -> Result<Vec<CheckResult>, PgExtrasError> {
-    run_diagnose(pool).await
+    PgExtras::new(pool.clone()).await?.diagnose().await
 }
 
 #[derive(Debug, Clone)]
@@ -314,16 +525,129 @@ pub async fn diagnose(pool: &Pool<Postgres>) -> Result<Vec<CheckResult>, PgExtra
 pub enum PgExtrasError {
     MissingConfigVars(),
     DbConnectionError(String),
+    /// A query parameter could not be parsed, e.g. a non-numeric limit.
+    InvalidParam {
+        /// The parameter the value was given for.
+        param: QueryParam,
+        /// The value as it was passed.
+        value: String,
+    },
+    /// The server cancelled the query after its statement or lock timeout.
+    Timeout {
+        /// Name of the cancelled query.
+        query: String,
+    },
+    /// The query needs an extension that is not installed in the database.
+    /// `source` is `None` when the missing extension was detected before running the query.
+    ExtensionMissing {
+        query: String,
+        extension: String,
+        source: Option<Arc<sqlx::Error>>,
+    },
+    /// The role lacks privileges, e.g. membership in `pg_read_all_stats`.
+    InsufficientPrivilege {
+        query: String,
+        message: String,
+        source: Arc<sqlx::Error>,
+    },
+    /// The query references catalog columns or functions missing on this server version.
+    UnsupportedServerVersion {
+        query: String,
+        server_version_num: i32,
+        message: String,
+        source: Arc<sqlx::Error>,
+    },
+    QueryFailed {
+        query: String,
+        sqlstate: Option<String>,
+        message: String,
+        source: Arc<sqlx::Error>,
+    },
+    /// The config file cannot be read or parsed, or names an unknown profile.
+    InvalidConfig(String),
+    /// The snapshot cannot be read, has an unsupported version or lacks the query.
+    InvalidSnapshot(String),
     Other(String),
 }
 
+impl PgExtrasError {
+    /// SQLSTATE code reported by the server, if the error came from it.
+    pub fn sqlstate(&self) -> Option<String> {
+        match self {
+            Self::QueryFailed { sqlstate, .. } => sqlstate.clone(),
+            _ => sqlstate(self.sqlx_error()?),
+        }
+    }
+
+    fn sqlx_error(&self) -> Option<&sqlx::Error> {
+        match self {
+            Self::ExtensionMissing { source, .. } => source.as_deref(),
+            Self::InsufficientPrivilege { source, .. }
+            | Self::UnsupportedServerVersion { source, .. }
+            | Self::QueryFailed { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn query_failed(query: &str, error: sqlx::Error) -> Self {
+        Self::QueryFailed {
+            query: query.to_string(),
+            sqlstate: sqlstate(&error),
+            message: database_message(&error),
+            source: Arc::new(error),
+        }
+    }
+}
+
 impl fmt::Display for PgExtrasError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let msg = match self {
             Self::MissingConfigVars() => {
-                "Both $DATABASE_URL and $PG_EXTRAS_DATABASE_URL are not set."
+                "No database connection configured. Checked: the --database-url, --host, --port, --user, --dbname and --service options, $PG_EXTRAS_DATABASE_URL, $DATABASE_URL, $PGSERVICE (pg_service.conf), $PGHOST, $PGHOSTADDR, $PGPORT, $PGUSER and $PGDATABASE."
             }
             Self::DbConnectionError(e) => &format!("Cannot connect to database: '{}'", e),
+            Self::InvalidParam { param, value } => &format!(
+                "Invalid value '{}' for parameter '{}', expected {}.",
+                value,
+                param,
+                param.expected()
+            ),
+            Self::Timeout { query } => &format!(
+                "Query '{}' was cancelled after exceeding its timeout.",
+                query
+            ),
+            Self::ExtensionMissing {
+                query, extension, ..
+            } => &format!(
+                "Query '{}' requires the '{}' extension. Install it with CREATE EXTENSION {};",
+                query, extension, extension
+            ),
+            Self::InsufficientPrivilege { query, message, .. } => &format!(
+                "Insufficient privilege to run query '{}': {}. Consider granting pg_read_all_stats or pg_monitor to this role.",
+                query, message
+            ),
+            Self::UnsupportedServerVersion {
+                query,
+                server_version_num,
+                message,
+                ..
+            } => &format!(
+                "Query '{}' is not supported by server version {}: {}",
+                query, server_version_num, message
+            ),
+            Self::QueryFailed {
+                query,
+                sqlstate,
+                message,
+                ..
+            } => &format!(
+                "Query '{}' failed ({}): {}",
+                query,
+                sqlstate.as_deref().unwrap_or("no SQLSTATE"),
+                message
+            ),
+            Self::InvalidConfig(e) => &format!("Invalid config: {}", e),
+            Self::InvalidSnapshot(e) => &format!("Invalid snapshot: {}", e),
             Self::Other(e) => &e.to_string(),
         };
 
@@ -331,9 +655,14 @@ impl fmt::Display for PgExtrasError {
     }
 }
 
-impl std::error::Error for PgExtrasError {}
+impl std::error::Error for PgExtrasError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.sqlx_error()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
 
-use crate::diagnose::run::{run_diagnose, CheckResult};
+use crate::diagnose::run::CheckResult;
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -341,52 +670,161 @@ lazy_static! {
     pub static ref PG_STAT_STATEMENTS_17: Version = semver::Version::parse("1.11.0").unwrap();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PgStatsVersion {
     Legacy,
     Standard,
     Pg17,
 }
 
-async fn get_rows<T: Query>(
-    params: Option<HashMap<String, String>>,
-    pool: &Pool<Postgres>,
-) -> Result<Vec<T>, PgExtrasError> {
-    let pg_statements_query =
-        "select installed_version from pg_available_extensions where name='pg_stat_statements'";
-
-    let pg_statements_version = match sqlx::query(pg_statements_query).fetch_one(pool).await {
-        Ok(row) => row
-            .try_get::<String, _>("installed_version")
-            .unwrap_or_default(),
-        Err(_) => "".to_string(),
+async fn get_rows<'a, T, A>(
+    params: Option<HashMap<QueryParam, String>>,
+    capabilities: Option<&ServerCapabilities>,
+    timeouts: &Timeouts,
+    conn: A,
+) -> Result<Vec<T>, PgExtrasError>
+where
+    T: Query,
+    A: Acquire<'a, Database = Postgres>,
+{
+    let mut conn = conn
+        .acquire()
+        .await
+        .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))?;
+
+    let detected;
+    let capabilities = match capabilities {
+        Some(capabilities) => capabilities,
+        None => {
+            detected = ServerCapabilities::detect(&mut *conn).await?;
+            &detected
+        }
     };
 
-    let default_version = NEW_PG_STAT_STATEMENTS.clone();
-    let pg_statements_version = format!("{}.0", pg_statements_version);
-    let pg_statements_version =
-        Version::parse(&pg_statements_version).unwrap_or(default_version.clone());
-
-    let pg_statements_version = if pg_statements_version < default_version {
-        PgStatsVersion::Legacy
-    } else if pg_statements_version >= *PG_STAT_STATEMENTS_17 {
-        PgStatsVersion::Pg17
-    } else {
-        PgStatsVersion::Standard
-    };
+    if let Some(extension) = T::required_extensions()
+        .into_iter()
+        .find(|extension| !capabilities.has_extension(extension))
+    {
+        return Err(PgExtrasError::ExtensionMissing {
+            query: T::name().to_string(),
+            extension: extension.to_string(),
+            source: None,
+        });
+    }
 
-    let mut query = T::read_file(Some(pg_statements_version));
+    let mut params = params.unwrap_or_default();
+    let values = T::params()
+        .into_iter()
+        .map(|param| param.parse(params.remove(&param)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sql = T::read_file(Some(capabilities));
+    let mut query = sqlx::query(&sql);
+    for value in values {
+        query = match value {
+            ParamValue::Text(text) => query.bind(text),
+            ParamValue::Int(int) => query.bind(int),
+        };
+    }
 
-    if let Some(params) = params {
-        for (key, value) in &params {
-            query = query.replace(&format!("%{{{}}}", key), value.as_str());
+    match fetch_with_timeouts(&mut conn, query, timeouts).await {
+        Ok(rows) => Ok(rows.iter().map(T::new).collect()),
+        Err(e) => Err(query_error(
+            T::name(),
+            &T::required_extensions(),
+            e,
+            capabilities,
+            timeouts,
+        )),
+    }
+}
+
+/// Runs `query`, applying the timeouts with `SET LOCAL` when any are set.
+async fn fetch_with_timeouts(
+    conn: &mut PgConnection,
+    query: sqlx::query::Query<'_, Postgres, PgArguments>,
+    timeouts: &Timeouts,
+) -> Result<Vec<PgRow>, sqlx::Error> {
+    if timeouts.is_empty() {
+        return query.fetch_all(conn).await;
+    }
+
+    // SET LOCAL settings are reverted together with the transaction (or savepoint
+    // if the caller passed an open transaction), leaving the connection untouched.
+    let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+    sqlx::query(SET_TIMEOUTS_QUERY)
+        .bind(
+            timeouts
+                .statement_timeout
+                .map(|t| format!("{}ms", t.as_millis())),
+        )
+        .bind(
+            timeouts
+                .lock_timeout
+                .map(|t| format!("{}ms", t.as_millis())),
+        )
+        .execute(&mut *tx)
+        .await?;
+    let rows = query.fetch_all(&mut *tx).await;
+    let rollback = tx.rollback().await;
+    rows.and_then(|rows| rollback.map(|_| rows))
+}
+
+const SET_TIMEOUTS_QUERY: &str = "
+SELECT
+  set_config('statement_timeout', coalesce($1, current_setting('statement_timeout')), true),
+  set_config('lock_timeout', coalesce($2, current_setting('lock_timeout')), true)";
+
+fn query_error(
+    name: &str,
+    required_extensions: &[&str],
+    error: sqlx::Error,
+    capabilities: &ServerCapabilities,
+    timeouts: &Timeouts,
+) -> PgExtrasError {
+    let query = name.to_string();
+
+    match sqlstate(&error).as_deref() {
+        // query_canceled is also raised by pg_cancel_backend, so only report a timeout
+        // when one was requested.
+        Some("57014") if timeouts.statement_timeout.is_some() => PgExtrasError::Timeout { query },
+        Some("55P03") if timeouts.lock_timeout.is_some() => PgExtrasError::Timeout { query },
+        Some("42501") => PgExtrasError::InsufficientPrivilege {
+            query,
+            message: database_message(&error),
+            source: Arc::new(error),
+        },
+        // undefined_table or undefined_function, e.g. an extension dropped after detection
+        Some("42P01") | Some("42883") if !required_extensions.is_empty() => {
+            PgExtrasError::ExtensionMissing {
+                query,
+                extension: required_extensions[0].to_string(),
+                source: Some(Arc::new(error)),
+            }
         }
+        // undefined_column, the catalog views differ on this server version
+        Some("42703") => PgExtrasError::UnsupportedServerVersion {
+            query,
+            server_version_num: capabilities.server_version_num,
+            message: database_message(&error),
+            source: Arc::new(error),
+        },
+        _ => PgExtrasError::query_failed(name, error),
     }
+}
+
+fn sqlstate(error: &sqlx::Error) -> Option<String> {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .map(|code| code.to_string())
+}
 
-    Ok(match sqlx::query(&query).fetch_all(pool).await {
-        Ok(rows) => rows.iter().map(T::new).collect(),
-        Err(e) => return Err(PgExtrasError::Other(format!("{}", e))),
-    })
+fn database_message(error: &sqlx::Error) -> String {
+    match error.as_database_error() {
+        Some(e) => e.message().to_string(),
+        None => format!("{}", error),
+    }
 }
 
 fn db_url() -> Result<String, PgExtrasError> {
@@ -395,26 +833,27 @@ fn db_url() -> Result<String, PgExtrasError> {
         .map_err(|_| PgExtrasError::MissingConfigVars())
 }
 
-fn schema_params(schema_name: Option<String>) -> HashMap<String, String> {
-    let schema_name = schema_name.unwrap_or(get_default_schema());
-    [("schema".to_string(), schema_name.to_string())]
-        .iter()
-        .cloned()
-        .collect()
+fn schema_params(schema_name: Option<String>) -> HashMap<QueryParam, String> {
+    param_args(QueryParam::Schema, schema_name)
 }
 
-fn limit_params(limit: Option<String>) -> HashMap<String, String> {
-    let limit = limit.unwrap_or("10".to_string());
-    [("limit".to_string(), limit.to_string())]
-        .iter()
-        .cloned()
-        .collect()
+fn limit_params(limit: Option<String>) -> HashMap<QueryParam, String> {
+    param_args(QueryParam::Limit, limit)
+}
+
+fn param_args(param: QueryParam, value: Option<String>) -> HashMap<QueryParam, String> {
+    value.map(|value| (param, value)).into_iter().collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::diagnose::report::render_diagnose_report;
+    use crate::diagnose::report::{diagnose_output, render_diagnose_report};
+    use crate::diagnose::run::{
+        run_diagnose, Check, CheckFuture, CheckOutcome, DiagnoseCheck, Severity,
+    };
+    use sqlx::postgres::PgPoolOptions;
+    use std::time::Duration;
 
     async fn setup() -> Result<(), Box<dyn std::error::Error>> {
         let port = match env::var("PG_VERSION").expect("PG_VERSION not set").as_str() {
@@ -449,6 +888,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn it_works() -> Result<(), Box<dyn std::error::Error>> {
         setup().await?;
 
@@ -492,6 +932,744 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn it_runs_registered_queries() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        for kind in QueryKind::ALL {
+            let output = kind.run(&client, HashMap::new()).await?;
+            assert_eq!(output.headers, kind.headers());
+            assert_eq!(output.rows.len(), output.cells.len());
+            for cells in &output.cells {
+                assert_eq!(cells.len(), output.headers.len(), "{}", kind);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_reuses_capabilities() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        let capabilities = client.capabilities();
+
+        assert!(capabilities.server_major_version() >= 12);
+        assert!(capabilities.has_extension("pg_stat_statements"));
+        assert!(!capabilities.in_recovery);
+
+        render_table(client.calls(None).await?);
+        render_table(client.outliers().await?);
+        render_diagnose_report(client.diagnose().await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_diagnoses_with_custom_thresholds() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        let table_cache_hit = |results: Vec<CheckResult>| {
+            results
+                .into_iter()
+                .find(|result| result.check == Check::TableCacheHit)
+                .unwrap()
+        };
+
+        let mut config = DiagnoseConfig::default();
+        config.set("table_cache_hit", "0")?;
+        // the critical default is above the warning threshold now
+        assert!(matches!(
+            run_diagnose(&client, &config).await,
+            Err(PgExtrasError::InvalidConfig(_))
+        ));
+        config.set("table_cache_hit_critical", "0")?;
+        let result = table_cache_hit(run_diagnose(&client, &config).await?);
+        assert_eq!(result.severity, Severity::Ok);
+        assert_eq!(result.threshold.unwrap().warning, 0.0);
+
+        config.set("table_cache_hit", "1.01")?;
+        let result = table_cache_hit(run_diagnose(&client, &config).await?);
+        assert_eq!(result.severity, Severity::Warning);
+        assert_eq!(result.threshold.unwrap().warning, 1.01);
+
+        config.set("table_cache_hit_critical", "1.005")?;
+        let result = table_cache_hit(run_diagnose(&client, &config).await?);
+        assert_eq!(result.severity, Severity::Critical);
+
+        Ok(())
+    }
+
+    struct TablesExist;
+
+    impl DiagnoseCheck for TablesExist {
+        fn name(&self) -> &str {
+            "tables_exist"
+        }
+
+        fn run<'a>(&'a self, client: &'a PgExtras) -> CheckFuture<'a> {
+            Box::pin(async move {
+                let tables: i64 = sqlx::query_scalar(
+                    "SELECT count(*) FROM pg_tables WHERE tablename = 'no_such_table'",
+                )
+                .fetch_one(client.pool())
+                .await
+                .map_err(|e| PgExtrasError::query_failed("tables_exist", e))?;
+                Ok(CheckOutcome {
+                    severity: Severity::Warning,
+                    message: format!("{} tables found", tables),
+                })
+            })
+        }
+
+        fn recommendation(&self) -> (&str, Vec<&str>) {
+            ("Create the table", vec!["Run the migrations."])
+        }
+    }
+
+    #[tokio::test]
+    async fn it_runs_custom_checks() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?.with_check(TablesExist);
+        let results = client.diagnose().await?;
+        let custom = results.last().unwrap();
+        assert_eq!(custom.check.to_string(), "tables_exist");
+        assert_eq!(custom.severity, Severity::Warning);
+        assert_eq!(custom.message, "0 tables found");
+
+        let output = diagnose_output(&results);
+        let row = output
+            .rows
+            .iter()
+            .find(|row| row["check"] == "tables_exist")
+            .unwrap();
+        assert_eq!(
+            row["recommendation"],
+            "Create the table:\n• Run the migrations."
+        );
+        render_diagnose_report(results);
+
+        Ok(())
+    }
+
+    struct BrokenCheck;
+
+    impl DiagnoseCheck for BrokenCheck {
+        fn name(&self) -> &str {
+            "broken"
+        }
+
+        fn run<'a>(&'a self, client: &'a PgExtras) -> CheckFuture<'a> {
+            Box::pin(async move {
+                sqlx::query("SELECT * FROM no_such_table")
+                    .execute(client.pool())
+                    .await
+                    .map_err(|e| PgExtrasError::query_failed("broken", e))?;
+                unreachable!()
+            })
+        }
+
+        fn recommendation(&self) -> (&str, Vec<&str>) {
+            ("Fix the check", vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_failing_custom_checks() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect()
+            .await?
+            .with_check(BrokenCheck)
+            .with_check(TablesExist);
+        let results = client.diagnose().await?;
+        assert!(results
+            .iter()
+            .any(|result| result.check == Check::TableCacheHit));
+
+        let broken = &results[results.len() - 2];
+        assert_eq!(broken.check.to_string(), "broken");
+        assert_eq!(broken.severity, Severity::Critical);
+        assert!(
+            broken
+                .message
+                .starts_with("Check failed: Query 'broken' failed (42P01)"),
+            "{}",
+            broken.message
+        );
+        // the checks registered after the failing one still run
+        assert_eq!(results.last().unwrap().check.to_string(), "tables_exist");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_runs_custom_queries() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let roles = CustomQuery::parse(
+            "superusers",
+            "/* Roles with superuser privileges */
+-- check: fail_if_rows > 0
+-- recommendation: Remove superuser from application roles
+
+SELECT rolname, rolsuper, NULL AS comment
+FROM pg_roles
+WHERE rolsuper
+ORDER BY rolname;",
+        )?;
+        let empty = CustomQuery::parse(
+            "no_rows",
+            "/* Nothing */\nSELECT 1 AS one, 'a' AS two WHERE false",
+        )?;
+        let client = PgExtras::connect()
+            .await?
+            .with_custom_query(roles.clone())
+            .with_custom_query(empty.clone());
+        assert_eq!(client.checks().names(), vec!["superusers"]);
+
+        let output = roles.run(&client).await?;
+        assert_eq!(output.description, "Roles with superuser privileges");
+        assert_eq!(output.headers, vec!["rolname", "rolsuper", "comment"]);
+        assert_eq!(output.cells[0], vec!["postgres", "true", ""]);
+
+        let output = client.custom_query("no_rows").unwrap().run(&client).await?;
+        assert_eq!(output.headers, vec!["one", "two"]);
+        assert!(output.rows.is_empty());
+
+        let results = client.diagnose().await?;
+        let custom = results.last().unwrap();
+        assert_eq!(custom.check.to_string(), "superusers");
+        assert_eq!(custom.severity, Severity::Warning);
+        assert!(custom.message.contains("postgres, true"));
+        let output = diagnose_output(&results);
+        let row = output
+            .rows
+            .iter()
+            .find(|row| row["check"] == "superusers")
+            .unwrap();
+        assert_eq!(
+            row["recommendation"],
+            "Remove superuser from application roles:\n"
+        );
+
+        // checks run with the timeouts of the client
+        let slow = CustomQuery::parse(
+            "slow",
+            "/* Slow */\n-- check: fail_if_rows > 0\nSELECT pg_sleep(5)",
+        )?;
+        let timeouts = Timeouts {
+            statement_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let client = PgExtras::connect()
+            .await?
+            .with_query_timeouts("slow", timeouts)
+            .with_custom_query(slow);
+        let results = client.diagnose().await?;
+        let slow = results.last().unwrap();
+        assert_eq!(slow.severity, Severity::Critical);
+        assert_eq!(
+            slow.message,
+            "Check failed: Query 'slow' was cancelled after exceeding its timeout."
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_runs_on_every_database() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?.with_default_schema("pg_catalog");
+        let databases = client.databases().await?;
+        let current: String = sqlx::query_scalar("SELECT current_database()")
+            .fetch_one(client.pool())
+            .await?;
+        assert!(databases.contains(&current));
+        assert!(!databases.iter().any(|name| name.starts_with("template")));
+
+        let results = client
+            .for_each_database(|client| async move {
+                let database: String = sqlx::query_scalar("SELECT current_database()")
+                    .fetch_one(client.pool())
+                    .await
+                    .map_err(|e| PgExtrasError::Other(e.to_string()))?;
+                let seq_scans = client.seq_scans(None).await?;
+                Ok((database, seq_scans))
+            })
+            .await?;
+
+        assert_eq!(results.len(), databases.len());
+        for (database, result) in results {
+            let (connected_to, seq_scans) = result?;
+            assert_eq!(connected_to, database);
+            // the default schema is kept, pg_catalog has no user tables
+            assert!(seq_scans.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_captures_snapshots() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        let snapshot = Snapshot::from_json(&Snapshot::capture(&client).await?.to_json())?;
+        assert!(snapshot.skipped.is_empty());
+        assert!(!snapshot.db_settings.is_empty());
+        assert!(snapshot.pg_stat_statements_version.is_some());
+
+        // every captured row deserializes back into its query struct
+        for kind in QueryKind::ALL {
+            let output = kind.from_snapshot(&snapshot)?;
+            assert_eq!(output.rows.len(), snapshot.queries[kind.name()].rows.len());
+        }
+
+        let live = client.diagnose().await?;
+        let offline = snapshot.diagnose(client.diagnose_config()).await?;
+        let checks = |results: &[CheckResult]| {
+            results
+                .iter()
+                .map(|result| result.check.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(checks(&offline), checks(&live));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_samples_rates() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        for query in RateQuery::ALL {
+            let rates = client
+                .rates(query.kind(), HashMap::new(), Duration::from_millis(50))
+                .await?;
+            assert!(rates.window >= Duration::from_millis(50));
+            assert!(rates.output.description.contains("over the last"));
+        }
+
+        let cache_hit = client
+            .rates(QueryKind::CacheHit, HashMap::new(), Duration::ZERO)
+            .await?;
+        assert_eq!(cache_hit.output.rows.len(), 2);
+        assert!(client
+            .rates(QueryKind::Bloat, HashMap::new(), Duration::ZERO)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_samples_rates_with_timeouts() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        // waiting for the lock on the view keeps the sample running past the timeout
+        let pool = pg_pool().await?;
+        let mut locker = pool.begin().await?;
+        sqlx::query("LOCK TABLE pg_stat_statements IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut *locker)
+            .await?;
+
+        let client = PgExtras::new(pool.clone()).await?.with_timeouts(Timeouts {
+            statement_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let result = client
+            .rates(QueryKind::Calls, HashMap::new(), Duration::ZERO)
+            .await;
+        locker.rollback().await?;
+        assert!(
+            matches!(&result, Err(PgExtrasError::Timeout { query }) if query == "calls"),
+            "{:?}",
+            result.map(|rates| rates.output)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_cancels_backends() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        let mut sleeper = pg_pool().await?.acquire().await?;
+        // pool connections share the client application_name, which protects them
+        sqlx::query("SET application_name = 'pg_extras_sleeper'")
+            .execute(&mut *sleeper)
+            .await?;
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(&mut *sleeper)
+            .await?;
+        let sleep = tokio::spawn(async move {
+            sqlx::query("SELECT pg_sleep(30)")
+                .execute(&mut *sleeper)
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let filter = BackendFilter {
+            pids: vec![pid],
+            state: Some("active".to_string()),
+            ..Default::default()
+        };
+        // the test role is a superuser
+        let selected = client.find_backends(&filter).await?;
+        assert!(selected.targets.is_empty());
+        assert_eq!(selected.protected[0].1, Protection::Superuser);
+
+        let filter = BackendFilter {
+            allow_superuser: true,
+            ..filter
+        };
+        let selected = client.find_backends(&filter).await?;
+        assert_eq!(selected.targets.len(), 1);
+
+        // the protections and the backend identity are checked again when signalling
+        let protected = BackendFilter {
+            allow_superuser: false,
+            ..filter.clone()
+        };
+        let results = client
+            .signal_backends(BackendSignal::Cancel, &protected, &selected.targets)
+            .await;
+        assert!(matches!(results[..], [(_, Ok(false))]));
+        let mut replaced = selected.targets[0].clone();
+        replaced.backend_start += 1;
+        let results = client
+            .signal_backends(BackendSignal::Cancel, &filter, &[replaced])
+            .await;
+        assert!(matches!(results[..], [(_, Ok(false))]));
+        assert!(!sleep.is_finished());
+
+        let results = client
+            .signal_backends(BackendSignal::Cancel, &filter, &selected.targets)
+            .await;
+        assert!(matches!(results[..], [(signalled, Ok(true))] if signalled == pid));
+        let error = sleep.await?.unwrap_err();
+        assert_eq!(
+            error.as_database_error().and_then(|e| e.code()).as_deref(),
+            Some("57014")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_does_not_signal_own_connections() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        // two connections of the client pool, idle once released
+        let client = PgExtras::connect().await?;
+        let mut first = client.pool().acquire().await?;
+        let mut second = client.pool().acquire().await?;
+        let mut pids = vec![];
+        for conn in [&mut first, &mut second] {
+            let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+                .fetch_one(&mut **conn)
+                .await?;
+            pids.push(pid);
+        }
+        drop((first, second));
+
+        let filter = BackendFilter {
+            pids: pids.clone(),
+            allow_superuser: true,
+            ..Default::default()
+        };
+        let selected = client.find_backends(&filter).await?;
+        assert!(selected.targets.is_empty());
+        assert_eq!(selected.protected.len(), 2);
+        assert!(selected
+            .protected
+            .iter()
+            .all(|(_, reason)| *reason == Protection::OwnConnection));
+
+        // signalling them anyway is refused, whichever connection runs the check
+        let backends = selected
+            .protected
+            .into_iter()
+            .map(|(backend, _)| backend)
+            .collect::<Vec<_>>();
+        let results = client
+            .signal_backends(BackendSignal::Terminate, &filter, &backends)
+            .await;
+        assert!(results
+            .iter()
+            .all(|(_, result)| matches!(result, Ok(false))));
+        assert_eq!(client.find_backends(&filter).await?.protected.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_resets_stats() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS reset_stats_test (id int)")
+            .execute(client.pool())
+            .await?;
+        client
+            .reset_stats(&StatsReset::Table("reset_stats_test".to_string()))
+            .await?;
+        // an unknown query of the current user leaves the others untouched
+        client
+            .reset_stats(&StatsReset::Statements {
+                user: Some("postgres".to_string()),
+                queryid: Some(1),
+            })
+            .await?;
+        client
+            .reset_stats(&StatsReset::Shared(SharedStats::Wal))
+            .await?;
+
+        let io = client
+            .reset_stats(&StatsReset::Shared(SharedStats::Io))
+            .await;
+        assert_eq!(
+            io.is_ok(),
+            client.capabilities().server_major_version() >= 16
+        );
+        let checkpointer = client
+            .reset_stats(&StatsReset::Shared(SharedStats::Checkpointer))
+            .await;
+        assert_eq!(
+            checkpointer.is_ok(),
+            client.capabilities().server_version_num >= 170000
+        );
+        assert!(matches!(
+            client
+                .reset_stats(&StatsReset::Table("missing_table".to_string()))
+                .await,
+            Err(PgExtrasError::QueryFailed { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_selects_server_variants() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let client = PgExtras::connect().await?;
+        let capabilities = client.capabilities();
+        let server_version: i32 = env::var("PG_VERSION")?.parse()?;
+        assert_eq!(capabilities.server_major_version(), server_version);
+
+        let (vacuum_stats_sql, calls_sql, outliers_sql) = match server_version {
+            12 => (
+                include_str!("sql/vacuum_stats.sql"),
+                include_str!("sql/calls_legacy.sql"),
+                include_str!("sql/outliers_legacy.sql"),
+            ),
+            13..=16 => (
+                include_str!("sql/vacuum_stats_13.sql"),
+                include_str!("sql/calls.sql"),
+                include_str!("sql/outliers.sql"),
+            ),
+            _ => (
+                include_str!("sql/vacuum_stats_13.sql"),
+                include_str!("sql/calls_17.sql"),
+                include_str!("sql/outliers_17.sql"),
+            ),
+        };
+        assert_eq!(VacuumStats::read_file(Some(capabilities)), vacuum_stats_sql);
+        assert_eq!(Calls::read_file(Some(capabilities)), calls_sql);
+        assert_eq!(Outliers::read_file(Some(capabilities)), outliers_sql);
+
+        client.vacuum_stats().await?;
+        client.calls(None).await?;
+        client.outliers().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn it_binds_params() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let pool = pg_pool().await?;
+
+        let injected = tables(Some("public' OR '1'='1".to_string()), &pool).await?;
+        assert!(injected.is_empty());
+
+        let invalid_limit = calls(Some("10; SELECT 1".to_string()), &pool).await;
+        assert!(matches!(
+            invalid_limit,
+            Err(PgExtrasError::InvalidParam {
+                param: QueryParam::Limit,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn it_runs_on_connections_and_transactions() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let pool = pg_pool().await?;
+
+        let mut conn = pool.acquire().await?;
+        assert!(!extensions(&mut *conn).await?.is_empty());
+        tables(None, &mut *conn).await?;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("SET LOCAL work_mem = '8MB'")
+            .execute(&mut *tx)
+            .await?;
+        assert!(!db_settings(&mut *tx).await?.is_empty());
+        calls(Some("5".to_string()), &mut *tx).await?;
+        let work_mem: String = sqlx::query_scalar("SHOW work_mem")
+            .fetch_one(&mut *tx)
+            .await?;
+        assert_eq!(work_mem, "8MB");
+        tx.rollback().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn it_applies_timeouts() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let pool = pg_pool().await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS pg_extras_lock_test (id integer)")
+            .execute(&pool)
+            .await?;
+
+        let mut locker = pool.begin().await?;
+        sqlx::query("LOCK TABLE pg_extras_lock_test IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut *locker)
+            .await?;
+
+        let client = PgExtras::new(pool.clone()).await?.with_timeouts(Timeouts {
+            lock_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let result = client.table_size().await;
+        assert!(
+            matches!(&result, Err(PgExtrasError::Timeout { query }) if query == "table_size"),
+            "{:?}",
+            result
+        );
+        locker.rollback().await?;
+        assert!(!client.extensions().await?.is_empty());
+
+        let result = client
+            .with_query_timeouts(
+                "mandelbrot",
+                Timeouts {
+                    statement_timeout: Some(Duration::from_millis(1)),
+                    ..Default::default()
+                },
+            )
+            .mandelbrot()
+            .await;
+        assert!(matches!(result, Err(PgExtrasError::Timeout { .. })));
+
+        let mut conn = pool.acquire().await?;
+        let lock_timeout: String = sqlx::query_scalar("SHOW lock_timeout")
+            .fetch_one(&mut *conn)
+            .await?;
+        assert_eq!(lock_timeout, "0");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn it_reports_failure_kinds() -> Result<(), Box<dyn std::error::Error>> {
+        setup().await?;
+
+        let pool = pg_pool().await?;
+        let timeouts = Timeouts::default();
+
+        let result = get_rows::<BuffercacheStats, _>(
+            None,
+            Some(&ServerCapabilities::default()),
+            &timeouts,
+            &pool,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(PgExtrasError::ExtensionMissing { extension, source: None, .. }) if extension == "pg_buffercache"
+        ));
+
+        sqlx::query(
+            "DO $$ BEGIN
+               IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'extras_unprivileged') THEN
+                 CREATE ROLE extras_unprivileged;
+               END IF;
+             END $$",
+        )
+        .execute(&pool)
+        .await?;
+        let mut tx = pool.begin().await?;
+        sqlx::query("SET LOCAL ROLE extras_unprivileged")
+            .execute(&mut *tx)
+            .await?;
+        let error = buffercache_stats(&mut *tx).await.unwrap_err();
+        assert!(matches!(error, PgExtrasError::InsufficientPrivilege { .. }));
+        assert_eq!(error.sqlstate().as_deref(), Some("42501"));
+        assert!(std::error::Error::source(&error).is_some());
+        tx.rollback().await?;
+
+        let mut capabilities = ServerCapabilities::detect(&pool).await?;
+        if capabilities.server_major_version() < 17 {
+            capabilities
+                .extensions
+                .insert("pg_stat_statements".to_string(), "1.11".to_string());
+            let result = get_rows::<Calls, _>(None, Some(&capabilities), &timeouts, &pool).await;
+            assert!(matches!(
+                result,
+                Err(PgExtrasError::UnsupportedServerVersion { .. })
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn serializes_raw_values() {
+        let calls = Calls {
+            qry: "SELECT 1".to_string(),
+            exec_time: Duration::from_millis(1500),
+            prop_exec_time: 0.25,
+            ncalls: 1000,
+            sync_io_time: Duration::ZERO,
+        };
+
+        assert_eq!(
+            calls.to_json(),
+            serde_json::json!({
+                "qry": "SELECT 1",
+                "exec_time": 1.5,
+                "prop_exec_time": 0.25,
+                "ncalls": 1000,
+                "sync_io_time": 0.0,
+            })
+        );
+        assert_eq!(
+            calls.to_row(),
+            row!["SELECT 1", "00:00:01.5", "25.0%", "1000", "00:00:00"]
+        );
+    }
+
     #[test]
     fn normal_types() {
         fn is_normal<T: Sized + Send + Sync + Unpin>() {}
@@ -0,0 +1,170 @@
+use crate::QueryOutput;
+use prettytable::{Cell, Row as TableRow, Table};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Box-drawn table with human-friendly values.
+    #[default]
+    Table,
+    /// Array of objects with raw values.
+    Json,
+    /// One JSON object with raw values per line.
+    Ndjson,
+    /// Header line followed by raw values.
+    Csv,
+    /// Markdown table with human-friendly values.
+    Markdown,
+}
+
+/// Writes the rows in the given format. Columns follow the order of `output.headers`.
+pub fn write_output<W: Write>(
+    output: &QueryOutput,
+    format: OutputFormat,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            output_table(output).print(writer)?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &ordered_rows(output))?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            for row in ordered_rows(output) {
+                serde_json::to_writer(&mut *writer, &row)?;
+                writeln!(writer)?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            csv.write_record(&output.headers)?;
+            for row in ordered_rows(output) {
+                csv.write_record(row.values().map(csv_value))?;
+            }
+            csv.flush()?;
+        }
+        OutputFormat::Markdown => {
+            writeln!(writer, "| {} |", output.headers.join(" | "))?;
+            writeln!(writer, "|{}", " --- |".repeat(output.headers.len()))?;
+            for cells in &output.cells {
+                let cells = cells.iter().map(|cell| markdown_cell(cell));
+                writeln!(writer, "| {} |", cells.collect::<Vec<_>>().join(" | "))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn output_table(output: &QueryOutput) -> Table {
+    let mut table = Table::new();
+    table.add_row(TableRow::new(
+        output
+            .headers
+            .iter()
+            .map(|header| Cell::new(header))
+            .collect(),
+    ));
+
+    let columns_count = output.headers.len();
+
+    for cells in &output.cells {
+        table.add_row(TableRow::new(
+            cells.iter().map(|cell| Cell::new(cell)).collect(),
+        ));
+    }
+    table
+        .set_titles(TableRow::new(vec![Cell::new(output.description.as_str())
+            .style_spec(format!("H{}", columns_count).as_str())]));
+    table
+}
+
+/// Raw rows with keys in `headers` order.
+fn ordered_rows(output: &QueryOutput) -> Vec<Map<String, Value>> {
+    output
+        .rows
+        .iter()
+        .map(|row| {
+            output
+                .headers
+                .iter()
+                .map(|header| (header.clone(), row.get(header).cloned().unwrap_or_default()))
+                .collect()
+        })
+        .collect()
+}
+
+fn csv_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn output() -> QueryOutput {
+        QueryOutput {
+            description: "Test".to_string(),
+            headers: vec!["name".to_string(), "size".to_string()],
+            rows: vec![
+                json!({ "size": 2048, "name": "users" }),
+                json!({ "size": null, "name": "a|b, \"c\"" }),
+            ],
+            cells: vec![
+                vec!["users".to_string(), "2048 bytes".to_string()],
+                vec!["a|b, \"c\"".to_string(), "".to_string()],
+            ],
+        }
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let mut buffer = Vec::new();
+        write_output(&output(), format, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn json_keeps_headers_order() {
+        assert_eq!(
+            render(OutputFormat::Ndjson),
+            "{\"name\":\"users\",\"size\":2048}\n{\"name\":\"a|b, \\\"c\\\"\",\"size\":null}\n"
+        );
+        let parsed: Value = serde_json::from_str(&render(OutputFormat::Json)).unwrap();
+        assert_eq!(parsed[0]["size"], json!(2048));
+    }
+
+    #[test]
+    fn csv_quotes_and_uses_raw_values() {
+        assert_eq!(
+            render(OutputFormat::Csv),
+            "name,size\nusers,2048\n\"a|b, \"\"c\"\"\",\n"
+        );
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        assert_eq!(
+            render(OutputFormat::Markdown),
+            "| name | size |\n| --- | --- |\n| users | 2048 bytes |\n| a\\|b, \"c\" |  |\n"
+        );
+    }
+
+    #[test]
+    fn table_has_title_and_headers() {
+        let table = render(OutputFormat::Table);
+        assert!(table.contains("Test"));
+        assert!(table.contains("2048 bytes"));
+    }
+}
@@ -1,31 +1,19 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::postgres::PgRow;
-use sqlx::types::BigDecimal;
 use sqlx::Row;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Bloat {
+    #[serde(rename = "type")]
     pub typefield: String,
     pub schemaname: String,
     pub object_name: String,
-    pub bloat: BigDecimal,
-    pub waste: String,
-}
-
-impl serde::Serialize for Bloat {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Bloat", 5)?;
-        state.serialize_field("typefield", &self.typefield)?;
-        state.serialize_field("schemaname", &self.schemaname)?;
-        state.serialize_field("object_name", &self.object_name)?;
-        state.serialize_field("bloat", &format!("{}", self.bloat))?;
-        state.serialize_field("waste", &self.waste)?;
-        state.end()
-    }
+    pub bloat: f64,
+    /// Wasted space in bytes.
+    pub waste: i64,
 }
 
 impl Query for Bloat {
@@ -44,8 +32,8 @@ impl Query for Bloat {
             self.typefield,
             self.schemaname,
             self.object_name,
-            self.bloat,
-            self.waste
+            format!("{:.1}", self.bloat),
+            format_bytes(self.waste)
         ]
     }
 
@@ -53,7 +41,11 @@ impl Query for Bloat {
         row!["type", "schemaname", "object_name", "bloat", "waste"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "bloat"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/bloat.sql").to_string()
     }
 }
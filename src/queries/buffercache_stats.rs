@@ -1,10 +1,13 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BuffercacheStats {
     pub relname: String,
-    pub buffered: String,
+    pub buffered: i64,
     pub buffer_percent: f64,
     pub percent_of_relation: f64,
 }
@@ -22,7 +25,7 @@ impl Query for BuffercacheStats {
     fn to_row(&self) -> prettytable::Row {
         row![
             self.relname,
-            self.buffered,
+            format_bytes(self.buffered),
             self.buffer_percent.to_string(),
             self.percent_of_relation.to_string()
         ]
@@ -37,7 +40,15 @@ impl Query for BuffercacheStats {
         ]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "buffercache_stats"
+    }
+
+    fn required_extensions() -> Vec<&'static str> {
+        vec!["pg_buffercache"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/buffercache_stats.sql")
             .to_string()
             .to_string()
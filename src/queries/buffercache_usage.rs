@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BuffercacheUsage {
     pub relname: String,
     pub buffers: i64,
@@ -23,7 +23,15 @@ impl Query for BuffercacheUsage {
         row!["relname", "buffers"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "buffercache_usage"
+    }
+
+    fn required_extensions() -> Vec<&'static str> {
+        vec!["pg_buffercache"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/buffercache_usage.sql").to_string()
     }
 }
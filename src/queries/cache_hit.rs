@@ -1,25 +1,14 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::postgres::PgRow;
-use sqlx::types::BigDecimal;
 use sqlx::Row;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CacheHit {
     pub name: String,
-    pub ratio: BigDecimal,
-}
-
-impl serde::Serialize for CacheHit {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("CacheHit", 2)?;
-        state.serialize_field("name", &self.name)?;
-        state.serialize_field("ratio", &format!("{:?}", self.ratio))?;
-        state.end()
-    }
+    pub ratio: f64,
 }
 
 impl Query for CacheHit {
@@ -38,7 +27,15 @@ impl Query for CacheHit {
         row!["name", "ratio"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "cache_hit"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/cache_hit.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Schema]
+    }
 }
@@ -1,53 +1,41 @@
-use crate::queries::shared::{get_default_interval, Query};
-use crate::PgStatsVersion;
-use sqlx::postgres::{types::PgInterval, PgRow};
+use crate::queries::shared::{
+    duration_secs, format_duration, format_percent, get_duration, Query, QueryParam,
+};
+use crate::{PgStatsVersion, ServerCapabilities};
+use sqlx::postgres::PgRow;
 use sqlx::Row;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Calls {
     pub qry: String,
-    pub exec_time: PgInterval,
-    pub prop_exec_time: String,
-    pub ncalls: String,
-    pub sync_io_time: PgInterval,
-}
-
-impl serde::Serialize for Calls {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Calls", 5)?;
-        state.serialize_field("qry", &self.qry)?;
-        state.serialize_field("exec_time", &format!("{:?}", self.exec_time))?;
-        state.serialize_field("prop_exec_time", &self.prop_exec_time)?;
-        state.serialize_field("ncalls", &self.ncalls)?;
-        state.serialize_field("sync_io_time", &format!("{:?}", self.sync_io_time))?;
-        state.end()
-    }
+    #[serde(with = "duration_secs")]
+    pub exec_time: Duration,
+    /// Share of the total execution time, from 0 to 1.
+    pub prop_exec_time: f64,
+    pub ncalls: i64,
+    #[serde(with = "duration_secs")]
+    pub sync_io_time: Duration,
 }
 
 impl Query for Calls {
     fn new(row: &PgRow) -> Self {
         Self {
             qry: row.try_get("qry").unwrap_or_default(),
-            exec_time: row.try_get("exec_time").unwrap_or(get_default_interval()),
+            exec_time: get_duration(row, "exec_time"),
             prop_exec_time: row.try_get("prop_exec_time").unwrap_or_default(),
             ncalls: row.try_get("ncalls").unwrap_or_default(),
-            sync_io_time: row
-                .try_get("sync_io_time")
-                .unwrap_or(get_default_interval()),
+            sync_io_time: get_duration(row, "sync_io_time"),
         }
     }
 
     fn to_row(&self) -> prettytable::Row {
         row![
             self.qry,
-            format!("{:?}", self.exec_time),
-            self.prop_exec_time,
+            format_duration(&self.exec_time),
+            format_percent(self.prop_exec_time),
             self.ncalls,
-            format!("{:?}", self.sync_io_time),
+            format_duration(&self.sync_io_time),
         ]
     }
 
@@ -61,10 +49,18 @@ impl Query for Calls {
         ]
     }
 
-    fn read_file(pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "calls"
+    }
+
+    fn required_extensions() -> Vec<&'static str> {
+        vec!["pg_stat_statements"]
+    }
+
+    fn read_file(capabilities: Option<&ServerCapabilities>) -> String {
         let default = include_str!("../sql/calls.sql");
 
-        match pg_statement_version {
+        match capabilities.map(|c| c.pg_stats_version()) {
             Some(PgStatsVersion::Legacy) => include_str!("../sql/calls_legacy.sql"),
             Some(PgStatsVersion::Standard) => default,
             Some(PgStatsVersion::Pg17) => include_str!("../sql/calls_17.sql"),
@@ -72,4 +68,8 @@ impl Query for Calls {
         }
         .to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Limit]
+    }
 }
@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Connections {
     pub username: String,
     pub pid: i32,
@@ -25,7 +25,15 @@ impl Query for Connections {
         row!["username", "pid", "client_addr"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "connections"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["pid"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/connections.sql").to_string()
     }
 }
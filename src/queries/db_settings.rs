@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DbSettings {
     pub name: String,
     pub setting: String,
@@ -27,7 +27,15 @@ impl Query for DbSettings {
         row!["name", "setting", "unit", "short_desc"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "db_settings"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["name"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/db_settings.sql").to_string()
     }
 }
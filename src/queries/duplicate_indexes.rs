@@ -1,9 +1,12 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DuplicateIndexes {
-    pub size: String,
+    pub size: i64,
     pub idx1: String,
     pub idx2: String,
     pub idx3: String,
@@ -22,14 +25,24 @@ impl Query for DuplicateIndexes {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.size, self.idx1, self.idx2, self.idx3, self.idx4]
+        row![
+            format_bytes(self.size),
+            self.idx1,
+            self.idx2,
+            self.idx3,
+            self.idx4
+        ]
     }
 
     fn headers() -> prettytable::Row {
         row!["size", "idx1", "idx2", "idx3", "idx4"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "duplicate_indexes"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/duplicate_indexes.sql").to_string()
     }
 }
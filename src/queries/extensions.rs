@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Extensions {
     pub name: String,
     pub default_version: String,
@@ -32,7 +32,11 @@ impl Query for Extensions {
         row!["name", "default_version", "installed_version", "comment"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "extensions"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/extensions.sql").to_string()
     }
 }
@@ -1,13 +1,17 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_optional, Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct IndexCacheHit {
     pub name: String,
     pub buffer_hits: i64,
     pub block_reads: i64,
     pub total_read: i64,
-    pub ratio: String,
+    /// `None` if there were no reads yet.
+    pub ratio: Option<f64>,
 }
 
 impl Query for IndexCacheHit {
@@ -17,7 +21,7 @@ impl Query for IndexCacheHit {
             buffer_hits: row.try_get("buffer_hits").unwrap_or_default(),
             block_reads: row.try_get("block_reads").unwrap_or_default(),
             total_read: row.try_get("total_read").unwrap_or_default(),
-            ratio: row.try_get("ratio").unwrap_or_default(),
+            ratio: row.try_get("ratio").ok().flatten(),
         }
     }
 
@@ -27,7 +31,7 @@ impl Query for IndexCacheHit {
             self.buffer_hits,
             self.block_reads,
             self.total_read,
-            self.ratio
+            format_optional(self.ratio)
         ]
     }
 
@@ -35,7 +39,15 @@ impl Query for IndexCacheHit {
         row!["name", "buffer_hits", "block_reads", "total_read", "ratio"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "index_cache_hit"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/index_cache_hit.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Schema]
+    }
 }
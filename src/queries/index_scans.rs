@@ -1,12 +1,15 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct IndexScans {
     pub schemaname: String,
     pub table: String,
     pub index: String,
-    pub index_size: String,
+    pub index_size: i64,
     pub index_scans: i64,
 }
 
@@ -26,7 +29,7 @@ impl Query for IndexScans {
             self.schemaname,
             self.table,
             self.index,
-            self.index_size,
+            format_bytes(self.index_size),
             self.index_scans.to_string()
         ]
     }
@@ -35,7 +38,15 @@ impl Query for IndexScans {
         row!["schemaname", "table", "index", "index_size", "index_scans"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "index_scans"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/index_scans.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Schema]
+    }
 }
@@ -1,10 +1,13 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct IndexSize {
     pub name: String,
-    pub size: String,
+    pub size: i64,
     pub schema: String,
 }
 
@@ -18,14 +21,18 @@ impl Query for IndexSize {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.name, self.size, self.schema]
+        row![self.name, format_bytes(self.size), self.schema]
     }
 
     fn headers() -> prettytable::Row {
         row!["name", "size", "schema"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "index_size"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/index_size.sql").to_string()
     }
 }
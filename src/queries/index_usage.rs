@@ -1,10 +1,14 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_optional, Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct IndexUsage {
     pub relname: String,
-    pub percent_of_times_index_used: String,
+    /// `None` if the table's indexes were never scanned.
+    pub percent_of_times_index_used: Option<i64>,
     pub rows_in_table: i64,
 }
 
@@ -12,9 +16,7 @@ impl Query for IndexUsage {
     fn new(row: &PgRow) -> Self {
         Self {
             relname: row.try_get("relname").unwrap_or_default(),
-            percent_of_times_index_used: row
-                .try_get("percent_of_times_index_used")
-                .unwrap_or_default(),
+            percent_of_times_index_used: row.try_get("percent_of_times_index_used").ok().flatten(),
             rows_in_table: row.try_get("rows_in_table").unwrap_or_default(),
         }
     }
@@ -22,7 +24,7 @@ impl Query for IndexUsage {
     fn to_row(&self) -> prettytable::Row {
         row![
             self.relname,
-            self.percent_of_times_index_used,
+            format_optional(self.percent_of_times_index_used),
             self.rows_in_table.to_string()
         ]
     }
@@ -31,7 +33,19 @@ impl Query for IndexUsage {
         row!["relname", "percent_of_times_index_used", "rows_in_table"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "index_usage"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["relname"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/index_usage.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Schema]
+    }
 }
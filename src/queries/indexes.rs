@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Indexes {
     pub schemaname: String,
     pub indexname: String,
@@ -32,7 +32,11 @@ impl Query for Indexes {
         row!["schemaname", "indexname", "tablename", "columns"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "indexes"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/indexes.sql").to_string()
     }
 }
@@ -1,9 +1,10 @@
-use crate::queries::shared::{get_default_interval, Query};
-use crate::PgStatsVersion;
-use sqlx::postgres::{types::PgInterval, PgRow};
+use crate::queries::shared::{duration_secs, format_duration, get_duration, Query};
+use crate::ServerCapabilities;
+use sqlx::postgres::PgRow;
 use sqlx::Row;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Locks {
     pub pid: i32,
     pub relname: String,
@@ -11,29 +12,11 @@ pub struct Locks {
     pub granted: bool,
     pub mode: String,
     pub query_snippet: String,
-    pub age: PgInterval,
+    #[serde(with = "duration_secs")]
+    pub age: Duration,
     pub application: String,
 }
 
-impl serde::Serialize for Locks {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Locks", 8)?;
-        state.serialize_field("pid", &self.pid)?;
-        state.serialize_field("relname", &self.relname)?;
-        state.serialize_field("transactionid", &self.transactionid)?;
-        state.serialize_field("granted", &self.granted)?;
-        state.serialize_field("mode", &self.mode)?;
-        state.serialize_field("query_snippet", &self.query_snippet)?;
-        state.serialize_field("age", &format!("{:?}", self.age))?;
-        state.serialize_field("application", &self.application)?;
-        state.end()
-    }
-}
-
 impl Query for Locks {
     fn new(row: &PgRow) -> Self {
         Self {
@@ -43,7 +26,7 @@ impl Query for Locks {
             granted: row.try_get("granted").unwrap_or_default(),
             mode: row.try_get("mode").unwrap_or_default(),
             query_snippet: row.try_get("query_snippet").unwrap_or_default(),
-            age: row.try_get("age").unwrap_or(get_default_interval()),
+            age: get_duration(row, "age"),
             application: row.try_get("application").unwrap_or_default(),
         }
     }
@@ -56,7 +39,7 @@ impl Query for Locks {
             self.granted,
             self.mode,
             self.query_snippet,
-            format!("{:?}", self.age),
+            format_duration(&self.age),
             self.application
         ]
     }
@@ -74,7 +57,11 @@ impl Query for Locks {
         ]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "locks"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/locks.sql").to_string()
     }
 }
@@ -1,47 +1,43 @@
-use crate::queries::shared::{get_default_interval, Query};
-use crate::PgStatsVersion;
-use sqlx::postgres::{types::PgInterval, PgRow};
+use crate::queries::shared::{duration_secs, format_duration, get_duration, Query};
+use crate::ServerCapabilities;
+use sqlx::postgres::PgRow;
 use sqlx::Row;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LongRunningQueries {
     pub pid: i32,
-    pub duration: PgInterval,
+    #[serde(with = "duration_secs")]
+    pub duration: Duration,
     pub query: String,
 }
 
-impl serde::Serialize for LongRunningQueries {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("LongRunningQueries", 3)?;
-        state.serialize_field("pid", &self.pid)?;
-        state.serialize_field("duration", &format!("{:?}", self.duration))?;
-        state.serialize_field("query", &self.query)?;
-        state.end()
-    }
-}
-
 impl Query for LongRunningQueries {
     fn new(row: &PgRow) -> Self {
         Self {
             pid: row.try_get("pid").unwrap_or_default(),
-            duration: row.try_get("duration").unwrap_or(get_default_interval()),
+            duration: get_duration(row, "duration"),
             query: row.try_get("query").unwrap_or_default(),
         }
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.pid, format!("{:?}", self.duration), self.query]
+        row![self.pid, format_duration(&self.duration), self.query]
     }
 
     fn headers() -> prettytable::Row {
         row!["pid", "duration", "query"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "long_running_queries"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["pid"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/long_running_queries.sql").to_string()
     }
 }
@@ -1,17 +1,21 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, format_percent, Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::postgres::types::Oid;
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct NullIndexes {
     pub oid: Oid,
     pub index: String,
-    pub index_size: String,
+    pub index_size: i64,
     pub unique: bool,
     pub indexed_column: String,
     pub table: String,
-    pub null_frac: String,
-    pub expected_saving: String,
+    /// Fraction of NULL values in the indexed column, from 0 to 1.
+    pub null_frac: f64,
+    pub expected_saving: i64,
     pub schema: String,
 }
 
@@ -32,14 +36,14 @@ impl Query for NullIndexes {
 
     fn to_row(&self) -> prettytable::Row {
         row![
-            format!("{:?}", self.oid),
+            self.oid.0,
             self.index,
-            self.index_size,
+            format_bytes(self.index_size),
             self.unique,
             self.indexed_column,
             self.table,
-            self.null_frac,
-            self.expected_saving,
+            format_percent(self.null_frac),
+            format_bytes(self.expected_saving),
             self.schema
         ]
     }
@@ -58,7 +62,15 @@ impl Query for NullIndexes {
         ]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "null_indexes"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/null_indexes.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::MinRelationSizeMb]
+    }
 }
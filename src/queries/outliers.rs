@@ -1,68 +1,68 @@
-use crate::queries::shared::{get_default_interval, Query};
-use crate::PgStatsVersion;
-use sqlx::postgres::{types::PgInterval, PgRow};
+use crate::queries::shared::{duration_secs, format_duration, format_percent, get_duration, Query};
+use crate::{PgStatsVersion, ServerCapabilities};
+use sqlx::postgres::PgRow;
 use sqlx::Row;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Outliers {
-    pub total_exec_time: PgInterval,
-    pub prop_exec_time: String,
-    pub ncalls: String,
-    pub sync_io_time: PgInterval,
+    #[serde(with = "duration_secs")]
+    pub total_exec_time: Duration,
+    /// Share of the total execution time, from 0 to 1.
+    pub prop_exec_time: f64,
+    pub ncalls: i64,
+    #[serde(with = "duration_secs")]
+    pub sync_io_time: Duration,
     pub query: String,
 }
 
-impl serde::Serialize for Outliers {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Outliers", 5)?;
-        state.serialize_field("total_exec_time", &format!("{:?}", self.total_exec_time))?;
-        state.end()
-    }
-}
-
 impl Query for Outliers {
     fn new(row: &PgRow) -> Self {
         Self {
-            total_exec_time: row
-                .try_get("total_exec_time")
-                .unwrap_or(get_default_interval()),
+            total_exec_time: get_duration(row, "total_exec_time"),
             prop_exec_time: row.try_get("prop_exec_time").unwrap_or_default(),
             ncalls: row.try_get("ncalls").unwrap_or_default(),
-            sync_io_time: row
-                .try_get("sync_io_time")
-                .unwrap_or(get_default_interval()),
+            sync_io_time: get_duration(row, "sync_io_time"),
             query: row.try_get("query").unwrap_or_default(),
         }
     }
 
     fn to_row(&self) -> prettytable::Row {
         row![
-            format!("{:?}", self.total_exec_time),
-            self.prop_exec_time,
+            format_duration(&self.total_exec_time),
+            format_percent(self.prop_exec_time),
             self.ncalls,
-            format!("{:?}", self.sync_io_time),
+            format_duration(&self.sync_io_time),
             self.query
         ]
     }
 
     fn headers() -> prettytable::Row {
         row![
-            "query",
-            "exec_time",
+            "total_exec_time",
             "prop_exec_time",
             "ncalls",
-            "sync_io_time"
+            "sync_io_time",
+            "query"
         ]
     }
 
-    fn read_file(pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "outliers"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["query"]
+    }
+
+    fn required_extensions() -> Vec<&'static str> {
+        vec!["pg_stat_statements"]
+    }
+
+    fn read_file(capabilities: Option<&ServerCapabilities>) -> String {
         let default = include_str!("../sql/outliers.sql");
 
-        match pg_statement_version {
+        match capabilities.map(|c| c.pg_stats_version()) {
             Some(PgStatsVersion::Legacy) => include_str!("../sql/outliers_legacy.sql"),
             Some(PgStatsVersion::Standard) => default,
             Some(PgStatsVersion::Pg17) => include_str!("../sql/outliers_17.sql"),
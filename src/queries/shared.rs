@@ -1,13 +1,30 @@
 use sqlx::postgres::{types::PgInterval, PgRow};
+use sqlx::Row;
 use std::env;
+use std::time::Duration;
 
-use crate::PgStatsVersion;
+use crate::{PgExtrasError, ServerCapabilities};
 
-pub trait Query: serde::Serialize {
+pub trait Query: serde::Serialize + serde::de::DeserializeOwned {
     fn new(row: &PgRow) -> Self;
     fn to_row(&self) -> prettytable::Row;
     fn headers() -> prettytable::Row;
-    fn read_file(pg_statement_version: Option<PgStatsVersion>) -> String;
+    /// Name of the query, matching its SQL file and CLI subcommand.
+    fn name() -> &'static str;
+    /// SQL of the variant matching the server, or the default one for `None`.
+    fn read_file(capabilities: Option<&ServerCapabilities>) -> String;
+    /// Parameters bound to the `$1`, `$2`, ... placeholders of the SQL file, in order.
+    fn params() -> Vec<QueryParam> {
+        vec![]
+    }
+    /// Extensions that must be installed for the query to run.
+    fn required_extensions() -> Vec<&'static str> {
+        vec![]
+    }
+    /// Columns identifying a row across snapshots and watch ticks, empty if rows can't be matched.
+    fn key_columns() -> Vec<&'static str> {
+        vec![]
+    }
     fn description() -> String {
         let file_content = Self::read_file(None);
         let desc = file_content.lines().take(1).next().unwrap_or_default();
@@ -19,6 +36,27 @@ pub trait Query: serde::Serialize {
     }
 }
 
+/// Picks the variant registered for the newest server major version that is not
+/// newer than the connected server, e.g. `&[(13, sql_13), (16, sql_16)]`.
+pub fn server_version_variant(
+    capabilities: Option<&ServerCapabilities>,
+    default: &'static str,
+    variants: &[(i32, &'static str)],
+) -> String {
+    let Some(capabilities) = capabilities else {
+        return default.to_string();
+    };
+    let server_version = capabilities.server_major_version();
+
+    variants
+        .iter()
+        .filter(|(min_version, _)| *min_version <= server_version)
+        .max_by_key(|(min_version, _)| *min_version)
+        .map(|(_, sql)| *sql)
+        .unwrap_or(default)
+        .to_string()
+}
+
 fn extract_desc(desc: &str) -> String {
     if let (Some(start), Some(end)) = (desc.find("/*"), desc.find("*/")) {
         let extracted = &desc[start + 2..end];
@@ -32,6 +70,79 @@ fn extract_desc(desc: &str) -> String {
     }
 }
 
+/// Parameter accepted by some of the queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryParam {
+    Schema,
+    Limit,
+    MinRelationSizeMb,
+}
+
+/// Typed value bound to a query placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamValue {
+    Text(String),
+    Int(i64),
+}
+
+impl QueryParam {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Schema => "schema",
+            Self::Limit => "limit",
+            Self::MinRelationSizeMb => "min_relation_size_mb",
+        }
+    }
+
+    pub fn expected(&self) -> &'static str {
+        match self {
+            Self::Schema => "a non-empty schema name",
+            Self::Limit => "a positive integer",
+            Self::MinRelationSizeMb => "a non-negative integer",
+        }
+    }
+
+    pub fn default_value(&self) -> String {
+        match self {
+            Self::Schema => get_default_schema(),
+            Self::Limit => "10".to_string(),
+            Self::MinRelationSizeMb => "10".to_string(),
+        }
+    }
+
+    /// Validates a raw value, falling back to the default when `None`.
+    pub fn parse(&self, value: Option<String>) -> Result<ParamValue, PgExtrasError> {
+        let value = value.unwrap_or_else(|| self.default_value());
+        let invalid = || PgExtrasError::InvalidParam {
+            param: *self,
+            value: value.clone(),
+        };
+
+        match self {
+            Self::Schema => {
+                if value.trim().is_empty() {
+                    return Err(invalid());
+                }
+                Ok(ParamValue::Text(value.clone()))
+            }
+            Self::Limit => match value.trim().parse::<i64>() {
+                Ok(limit) if limit > 0 => Ok(ParamValue::Int(limit)),
+                _ => Err(invalid()),
+            },
+            Self::MinRelationSizeMb => match value.trim().parse::<i64>() {
+                Ok(size) if size >= 0 => Ok(ParamValue::Int(size)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for QueryParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 pub fn get_default_interval() -> PgInterval {
     PgInterval {
         microseconds: 0,
@@ -43,3 +154,216 @@ pub fn get_default_interval() -> PgInterval {
 pub fn get_default_schema() -> String {
     env::var("PG_EXTRAS_SCHEMA").unwrap_or("public".to_string())
 }
+
+const MICROSECONDS_PER_DAY: i64 = 86_400_000_000;
+
+/// Reads an interval column as a `Duration`, negative intervals become zero.
+pub fn get_duration(row: &PgRow, column: &str) -> Duration {
+    let interval: PgInterval = row.try_get(column).unwrap_or(get_default_interval());
+    interval_to_duration(&interval)
+}
+
+/// Converts an interval to a `Duration`, counting a month as 30 days like PostgreSQL does.
+pub fn interval_to_duration(interval: &PgInterval) -> Duration {
+    let microseconds = interval.microseconds
+        + interval.days as i64 * MICROSECONDS_PER_DAY
+        + interval.months as i64 * 30 * MICROSECONDS_PER_DAY;
+    Duration::from_micros(microseconds.max(0) as u64)
+}
+
+/// Formats a size in bytes the same way as `pg_size_pretty`, e.g. `18 MB`.
+pub fn format_bytes(bytes: i64) -> String {
+    // (unit, limit, round, unit bits) as in PostgreSQL's dbsize.c
+    const UNITS: [(&str, i64, bool, u32); 6] = [
+        ("bytes", 10 * 1024, false, 0),
+        ("kB", 20 * 1024 - 1, true, 10),
+        ("MB", 20 * 1024 - 1, true, 20),
+        ("GB", 20 * 1024 - 1, true, 30),
+        ("TB", 20 * 1024 - 1, true, 40),
+        ("PB", 20 * 1024 - 1, true, 50),
+    ];
+    let half_rounded = |x: i64| (x + if x < 0 { -1 } else { 1 }) / 2;
+
+    let mut size = bytes;
+    for (i, (name, limit, round, unit_bits)) in UNITS.iter().enumerate() {
+        let Some((_, _, next_round, next_unit_bits)) = UNITS.get(i + 1) else {
+            break;
+        };
+        if size.abs() < *limit {
+            let size = if *round { half_rounded(size) } else { size };
+            return format!("{} {}", size, name);
+        }
+        let bits = next_unit_bits - unit_bits - *next_round as u32 + *round as u32;
+        size /= 1_i64 << bits;
+    }
+    format!("{} PB", half_rounded(size))
+}
+
+/// Formats a duration like a PostgreSQL interval, e.g. `1 day 02:03:04.5`.
+pub fn format_duration(duration: &Duration) -> String {
+    let total_seconds = duration.as_secs();
+    let days = total_seconds / 86_400;
+    let hours = (total_seconds % 86_400) / 3_600;
+    let minutes = (total_seconds % 3_600) / 60;
+    let seconds = total_seconds % 60;
+    let microseconds = duration.subsec_micros();
+
+    let mut formatted = match days {
+        0 => String::new(),
+        1 => "1 day ".to_string(),
+        _ => format!("{} days ", days),
+    };
+    formatted.push_str(&format!("{:02}:{:02}:{:02}", hours, minutes, seconds));
+    if microseconds > 0 {
+        let fraction = format!("{:06}", microseconds);
+        formatted.push('.');
+        formatted.push_str(fraction.trim_end_matches('0'));
+    }
+    formatted
+}
+
+/// Formats a ratio as a percentage, e.g. `0.123` as `12.3%`.
+pub fn format_percent(ratio: f64) -> String {
+    format!("{:.1}%", ratio * 100.0)
+}
+
+/// Formats a value that may be missing due to insufficient statistics.
+pub fn format_optional<T: std::fmt::Display>(value: Option<T>) -> String {
+    value
+        .map(|value| value.to_string())
+        .unwrap_or("Insufficient data".to_string())
+}
+
+/// Serializes a `Duration` as fractional seconds.
+pub mod duration_secs {
+    use std::time::Duration;
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let secs: f64 = serde::Deserialize::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_version_variants() {
+        let variants = [(16, "sql_16"), (13, "sql_13")];
+        let server = |server_version_num| ServerCapabilities {
+            server_version_num,
+            ..Default::default()
+        };
+
+        assert_eq!(server_version_variant(None, "sql", &variants), "sql");
+        assert_eq!(
+            server_version_variant(Some(&server(120020)), "sql", &variants),
+            "sql"
+        );
+        assert_eq!(
+            server_version_variant(Some(&server(150008)), "sql", &variants),
+            "sql_13"
+        );
+        assert_eq!(
+            server_version_variant(Some(&server(170000)), "sql", &variants),
+            "sql_16"
+        );
+    }
+
+    #[test]
+    fn format_bytes_like_pg_size_pretty() {
+        assert_eq!(format_bytes(0), "0 bytes");
+        assert_eq!(format_bytes(8192), "8192 bytes");
+        assert_eq!(format_bytes(16384), "16 kB");
+        assert_eq!(format_bytes(1_000_000), "977 kB");
+        assert_eq!(format_bytes(18_874_368), "18 MB");
+        assert_eq!(format_bytes(2 * 1024 * 1024 * 1024 * 1024), "2048 GB");
+        assert_eq!(format_bytes(-16384), "-16 kB");
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(&Duration::from_millis(500)), "00:00:00.5");
+        assert_eq!(format_duration(&Duration::from_secs(3_723)), "01:02:03");
+        assert_eq!(
+            format_duration(&Duration::from_micros(90_000_000_123)),
+            "1 day 01:00:00.000123"
+        );
+    }
+
+    #[test]
+    fn interval_conversion() {
+        let interval = PgInterval {
+            months: 0,
+            days: 1,
+            microseconds: 1_500_000,
+        };
+        assert_eq!(
+            interval_to_duration(&interval),
+            Duration::from_micros(86_401_500_000)
+        );
+
+        let negative = PgInterval {
+            months: 0,
+            days: 0,
+            microseconds: -1,
+        };
+        assert_eq!(interval_to_duration(&negative), Duration::ZERO);
+    }
+
+    #[test]
+    fn parse_limit() {
+        assert_eq!(
+            QueryParam::Limit.parse(Some("5".to_string())).unwrap(),
+            ParamValue::Int(5)
+        );
+        assert_eq!(QueryParam::Limit.parse(None).unwrap(), ParamValue::Int(10));
+    }
+
+    #[test]
+    fn parse_limit_rejects_sql() {
+        let result = QueryParam::Limit.parse(Some("1; DROP TABLE users".to_string()));
+        assert!(matches!(
+            result,
+            Err(PgExtrasError::InvalidParam {
+                param: QueryParam::Limit,
+                ..
+            })
+        ));
+        assert!(QueryParam::Limit.parse(Some("0".to_string())).is_err());
+    }
+
+    #[test]
+    fn parse_min_relation_size() {
+        assert_eq!(
+            QueryParam::MinRelationSizeMb
+                .parse(Some("0".to_string()))
+                .unwrap(),
+            ParamValue::Int(0)
+        );
+        assert!(QueryParam::MinRelationSizeMb
+            .parse(Some("-1".to_string()))
+            .is_err());
+    }
+
+    #[test]
+    fn parse_schema_keeps_value_verbatim() {
+        let schema = "public' OR '1'='1".to_string();
+        assert_eq!(
+            QueryParam::Schema.parse(Some(schema.clone())).unwrap(),
+            ParamValue::Text(schema)
+        );
+        assert!(QueryParam::Schema.parse(Some(" ".to_string())).is_err());
+    }
+}
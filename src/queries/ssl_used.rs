@@ -1,7 +1,7 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{queries::shared::Query, ServerCapabilities};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SslUsed {
     pub ssl_used: bool,
 }
@@ -21,7 +21,15 @@ impl Query for SslUsed {
         row!["ssl_used"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "ssl_used"
+    }
+
+    fn required_extensions() -> Vec<&'static str> {
+        vec!["sslinfo"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/ssl_used.sql").to_string()
     }
 }
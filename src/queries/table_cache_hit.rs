@@ -1,13 +1,17 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_optional, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TableCacheHit {
     pub name: String,
     pub buffer_hits: i64,
     pub block_reads: i64,
     pub total_read: i64,
-    pub ratio: String,
+    /// `None` if there were no reads yet.
+    pub ratio: Option<f64>,
 }
 
 impl Query for TableCacheHit {
@@ -17,7 +21,7 @@ impl Query for TableCacheHit {
             buffer_hits: row.try_get("buffer_hits").unwrap_or_default(),
             block_reads: row.try_get("block_reads").unwrap_or_default(),
             total_read: row.try_get("total_read").unwrap_or_default(),
-            ratio: row.try_get("ratio").unwrap_or_default(),
+            ratio: row.try_get("ratio").ok().flatten(),
         }
     }
 
@@ -27,7 +31,7 @@ impl Query for TableCacheHit {
             self.buffer_hits,
             self.block_reads,
             self.total_read,
-            self.ratio
+            format_optional(self.ratio)
         ]
     }
 
@@ -35,7 +39,11 @@ impl Query for TableCacheHit {
         row!["name", "buffer_hits", "block_reads", "total_read", "ratio"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "table_cache_hit"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/table_cache_hit.sql").to_string()
     }
 }
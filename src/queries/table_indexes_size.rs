@@ -1,10 +1,13 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TableIndexesSize {
     pub table: String,
-    pub index_size: String,
+    pub index_size: i64,
 }
 
 impl Query for TableIndexesSize {
@@ -16,14 +19,18 @@ impl Query for TableIndexesSize {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.table, self.index_size]
+        row![self.table, format_bytes(self.index_size)]
     }
 
     fn headers() -> prettytable::Row {
         row!["table", "index_size"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "table_indexes_size"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/table_indexes_size.sql").to_string()
     }
 }
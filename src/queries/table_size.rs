@@ -1,10 +1,13 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TableSize {
     pub name: String,
-    pub size: String,
+    pub size: i64,
     pub schema: String,
 }
 
@@ -18,14 +21,22 @@ impl Query for TableSize {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.name, self.size, self.schema]
+        row![self.name, format_bytes(self.size), self.schema]
     }
 
     fn headers() -> prettytable::Row {
         row!["name", "size", "schema"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "table_size"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["schema", "name"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/table_size.sql").to_string()
     }
 }
@@ -1,9 +1,12 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TotalIndexSize {
-    pub size: String,
+    pub size: i64,
 }
 
 impl Query for TotalIndexSize {
@@ -14,14 +17,18 @@ impl Query for TotalIndexSize {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.size]
+        row![format_bytes(self.size)]
     }
 
     fn headers() -> prettytable::Row {
         row!["size"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "total_index_size"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/total_index_size.sql").to_string()
     }
 }
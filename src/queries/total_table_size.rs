@@ -1,10 +1,13 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TotalTableSize {
     pub name: String,
-    pub size: String,
+    pub size: i64,
 }
 
 impl Query for TotalTableSize {
@@ -16,14 +19,18 @@ impl Query for TotalTableSize {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.name, self.size]
+        row![self.name, format_bytes(self.size)]
     }
 
     fn headers() -> prettytable::Row {
         row!["name", "size"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "total_table_size"
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/total_table_size.sql").to_string()
     }
 }
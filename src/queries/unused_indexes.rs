@@ -1,11 +1,14 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{format_bytes, Query, QueryParam},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct UnusedIndexes {
     pub table: String,
     pub index: String,
-    pub index_size: String,
+    pub index_size: i64,
     pub index_scans: i64,
 }
 
@@ -20,14 +23,31 @@ impl Query for UnusedIndexes {
     }
 
     fn to_row(&self) -> prettytable::Row {
-        row![self.table, self.index, self.index_size, self.index_scans]
+        row![
+            self.table,
+            self.index,
+            format_bytes(self.index_size),
+            self.index_scans
+        ]
     }
 
     fn headers() -> prettytable::Row {
         row!["table", "index", "index_size", "index_scans"]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
+    fn name() -> &'static str {
+        "unused_indexes"
+    }
+
+    fn key_columns() -> Vec<&'static str> {
+        vec!["table", "index"]
+    }
+
+    fn read_file(_capabilities: Option<&ServerCapabilities>) -> String {
         include_str!("../sql/unused_indexes.sql").to_string()
     }
+
+    fn params() -> Vec<QueryParam> {
+        vec![QueryParam::Schema]
+    }
 }
@@ -1,16 +1,19 @@
-use crate::{queries::shared::Query, PgStatsVersion};
+use crate::{
+    queries::shared::{server_version_variant, Query},
+    ServerCapabilities,
+};
 use sqlx::{postgres::PgRow, Row};
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct VacuumStats {
     pub schema: String,
     pub table: String,
     pub last_vacuum: String,
     pub last_autovacuum: String,
-    pub rowcount: String,
-    pub dead_rowcount: String,
-    pub autovacuum_threshold: String,
-    pub expect_autovacuum: String,
+    pub rowcount: i64,
+    pub dead_rowcount: i64,
+    pub autovacuum_threshold: i64,
+    pub expect_autovacuum: bool,
 }
 
 impl Query for VacuumStats {
@@ -36,7 +39,7 @@ impl Query for VacuumStats {
             self.rowcount,
             self.dead_rowcount,
             self.autovacuum_threshold,
-            self.expect_autovacuum
+            if self.expect_autovacuum { "yes" } else { "" }
         ]
     }
 
@@ -53,7 +56,15 @@ impl Query for VacuumStats {
         ]
     }
 
-    fn read_file(_pg_statement_version: Option<PgStatsVersion>) -> String {
-        include_str!("../sql/vacuum_stats.sql").to_string()
+    fn name() -> &'static str {
+        "vacuum_stats"
+    }
+
+    fn read_file(capabilities: Option<&ServerCapabilities>) -> String {
+        server_version_variant(
+            capabilities,
+            include_str!("../sql/vacuum_stats.sql"),
+            &[(13, include_str!("../sql/vacuum_stats_13.sql"))],
+        )
     }
 }
@@ -0,0 +1,534 @@
+use crate::queries::shared::{format_duration, format_percent};
+use crate::{
+    fetch_with_timeouts, ParamValue, PgExtras, PgExtrasError, PgStatsVersion, QueryKind,
+    QueryOutput, QueryParam, ServerCapabilities,
+};
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Queries reporting cumulative counters, which can be sampled to get per-second rates.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+/// use pg_extras::{render_output, PgExtras, QueryKind};
+/// use std::collections::HashMap;
+/// use std::time::Duration;
+///
+/// let client = PgExtras::connect().await?;
+/// let rates = client
+///     .rates(QueryKind::Calls, HashMap::new(), Duration::from_secs(30))
+///     .await?;
+/// render_output(&rates.output);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateQuery {
+    Calls,
+    Outliers,
+    SeqScans,
+    IndexScans,
+    TableIndexScans,
+    CacheHit,
+}
+
+/// Counters read at one point in time, keyed by queryid, relid or indexrelid.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub taken_at: Instant,
+    /// `stats_reset` of the underlying view, if the server reports it.
+    stats_reset: Option<String>,
+    rows: HashMap<i64, SampleRow>,
+}
+
+#[derive(Debug, Clone)]
+struct SampleRow {
+    labels: Vec<String>,
+    counters: Vec<f64>,
+}
+
+/// Rates computed from two samples.
+#[derive(Debug, Clone)]
+pub struct Rates {
+    pub window: Duration,
+    /// Entries whose counters went backwards, i.e. were reset during the window.
+    pub reset: usize,
+    /// Entries missing from the first sample, e.g. new statements or tables.
+    pub appeared: usize,
+    /// Entries missing from the second sample, e.g. evicted statements or dropped tables.
+    pub vanished: usize,
+    pub output: QueryOutput,
+}
+
+impl RateQuery {
+    pub const ALL: &'static [RateQuery] = &[
+        Self::Calls,
+        Self::Outliers,
+        Self::SeqScans,
+        Self::IndexScans,
+        Self::TableIndexScans,
+        Self::CacheHit,
+    ];
+
+    pub fn from_kind(kind: QueryKind) -> Option<Self> {
+        Self::ALL.iter().copied().find(|query| query.kind() == kind)
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        match self {
+            Self::Calls => QueryKind::Calls,
+            Self::Outliers => QueryKind::Outliers,
+            Self::SeqScans => QueryKind::SeqScans,
+            Self::IndexScans => QueryKind::IndexScans,
+            Self::TableIndexScans => QueryKind::TableIndexScans,
+            Self::CacheHit => QueryKind::CacheHit,
+        }
+    }
+
+    fn sql(&self, capabilities: &ServerCapabilities) -> &'static str {
+        match self {
+            Self::Calls | Self::Outliers => match capabilities.pg_stats_version() {
+                PgStatsVersion::Legacy => include_str!("sql/statement_counters_legacy.sql"),
+                PgStatsVersion::Standard => include_str!("sql/statement_counters.sql"),
+                PgStatsVersion::Pg17 => include_str!("sql/statement_counters_17.sql"),
+            },
+            Self::SeqScans | Self::TableIndexScans => include_str!("sql/table_counters.sql"),
+            Self::IndexScans => include_str!("sql/index_counters.sql"),
+            Self::CacheHit => include_str!("sql/cache_counters.sql"),
+        }
+    }
+
+    /// Query returning the last reset of the counters, `pg_stat_statements_info` needs 1.9.
+    fn stats_reset_sql(&self, capabilities: &ServerCapabilities) -> Option<&'static str> {
+        match self {
+            Self::Calls | Self::Outliers => {
+                let version = capabilities.extension_version("pg_stat_statements")?;
+                let version = semver::Version::parse(&format!("{}.0", version)).ok()?;
+                (version >= semver::Version::new(1, 9, 0))
+                    .then_some("SELECT stats_reset::text FROM pg_stat_statements_info")
+            }
+            _ => Some(
+                "SELECT stats_reset::text FROM pg_stat_database WHERE datname = current_database()",
+            ),
+        }
+    }
+
+    fn labels(&self) -> &'static [&'static str] {
+        match self {
+            Self::Calls | Self::Outliers => &["query"],
+            Self::SeqScans | Self::TableIndexScans => &["name"],
+            Self::IndexScans => &["schema", "table", "index"],
+            Self::CacheHit => &["name"],
+        }
+    }
+
+    fn counters(&self) -> &'static [&'static str] {
+        match self {
+            Self::Calls | Self::Outliers => &["calls", "exec_time", "sync_io_time"],
+            Self::SeqScans => &["seq_scan"],
+            Self::TableIndexScans | Self::IndexScans => &["idx_scan"],
+            Self::CacheHit => &["hit", "read"],
+        }
+    }
+
+    fn uses_schema(&self) -> bool {
+        !matches!(self, Self::Calls | Self::Outliers)
+    }
+
+    /// Reads the current counters.
+    pub async fn sample(
+        &self,
+        client: &PgExtras,
+        params: &HashMap<QueryParam, String>,
+    ) -> Result<Sample, PgExtrasError> {
+        let capabilities = client.capabilities();
+        for extension in self.kind().required_extensions() {
+            if !capabilities.has_extension(extension) {
+                return Err(PgExtrasError::ExtensionMissing {
+                    query: self.kind().name().to_string(),
+                    extension: extension.to_string(),
+                    source: None,
+                });
+            }
+        }
+
+        let timeouts = client.query_timeouts(self.kind().name());
+        let query_error = |e| {
+            crate::query_error(
+                self.kind().name(),
+                &self.kind().required_extensions(),
+                e,
+                capabilities,
+                &timeouts,
+            )
+        };
+        let mut conn = client
+            .pool()
+            .acquire()
+            .await
+            .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))?;
+
+        let mut query = sqlx::query(self.sql(capabilities));
+        if self.uses_schema() {
+            let schema = params
+                .get(&QueryParam::Schema)
+                .cloned()
+                .unwrap_or_else(|| client.default_schema());
+            query = query.bind(schema);
+        }
+        let rows = fetch_with_timeouts(&mut conn, query, &timeouts)
+            .await
+            .map_err(query_error)?;
+
+        let stats_reset = match self.stats_reset_sql(capabilities) {
+            Some(sql) => fetch_with_timeouts(&mut conn, sqlx::query(sql), &timeouts)
+                .await
+                .map_err(query_error)?
+                .first()
+                .and_then(|row| row.try_get(0).ok()),
+            None => None,
+        };
+
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let sample_row = SampleRow {
+                    labels: self
+                        .labels()
+                        .iter()
+                        .map(|label| row.try_get(*label).unwrap_or_default())
+                        .collect(),
+                    counters: self
+                        .counters()
+                        .iter()
+                        .map(|counter| row.try_get(*counter).unwrap_or_default())
+                        .collect(),
+                };
+                (row.try_get("key").unwrap_or_default(), sample_row)
+            })
+            .collect();
+
+        Ok(Sample {
+            taken_at: Instant::now(),
+            stats_reset,
+            rows,
+        })
+    }
+
+    /// Subtracts the samples by key. Entries reset in between count from zero, like new ones.
+    pub fn rates(
+        &self,
+        before: &Sample,
+        after: &Sample,
+        params: &HashMap<QueryParam, String>,
+    ) -> Result<Rates, PgExtrasError> {
+        let window = after.taken_at.saturating_duration_since(before.taken_at);
+        let all_reset = before.stats_reset != after.stats_reset;
+
+        let mut rates = Rates {
+            window,
+            reset: 0,
+            appeared: 0,
+            vanished: 0,
+            output: QueryOutput {
+                description: String::new(),
+                headers: vec![],
+                rows: vec![],
+                cells: vec![],
+            },
+        };
+
+        let mut deltas = vec![];
+        for (key, row) in &after.rows {
+            let counters = match before.rows.get(key) {
+                None => {
+                    rates.appeared += 1;
+                    row.counters.clone()
+                }
+                Some(previous)
+                    if all_reset
+                        || row
+                            .counters
+                            .iter()
+                            .zip(&previous.counters)
+                            .any(|(after, before)| after < before) =>
+                {
+                    rates.reset += 1;
+                    row.counters.clone()
+                }
+                Some(previous) => row
+                    .counters
+                    .iter()
+                    .zip(&previous.counters)
+                    .map(|(after, before)| after - before)
+                    .collect(),
+            };
+            deltas.push((&row.labels, counters));
+        }
+        rates.vanished = before
+            .rows
+            .keys()
+            .filter(|key| !after.rows.contains_key(key))
+            .count();
+
+        let seconds = window.as_secs_f64().max(f64::EPSILON);
+        let limit = match self {
+            Self::Calls => {
+                match QueryParam::Limit.parse(params.get(&QueryParam::Limit).cloned())? {
+                    ParamValue::Int(limit) => limit as usize,
+                    ParamValue::Text(_) => usize::MAX,
+                }
+            }
+            Self::Outliers => 20,
+            _ => usize::MAX,
+        };
+
+        let (headers, mut rows): (&[&str], Vec<Value>) = match self {
+            Self::Calls | Self::Outliers => {
+                // counters: calls, exec_time and sync_io_time, times in milliseconds
+                let total_exec_time: f64 = deltas.iter().map(|(_, counters)| counters[1]).sum();
+                let rows = deltas
+                    .iter()
+                    .filter(|(_, counters)| counters[0] > 0.0)
+                    .map(|(labels, counters)| {
+                        json!({
+                            "query": labels[0],
+                            "calls": counters[0] as i64,
+                            "calls_per_sec": counters[0] / seconds,
+                            "exec_time_per_sec": counters[1] / 1000.0 / seconds,
+                            "mean_exec_time": counters[1] / 1000.0 / counters[0],
+                            "prop_exec_time": if total_exec_time > 0.0 { counters[1] / total_exec_time } else { 0.0 },
+                            "sync_io_time_per_sec": counters[2] / 1000.0 / seconds,
+                        })
+                    })
+                    .collect();
+                (
+                    &[
+                        "query",
+                        "calls",
+                        "calls_per_sec",
+                        "exec_time_per_sec",
+                        "mean_exec_time",
+                        "prop_exec_time",
+                        "sync_io_time_per_sec",
+                    ],
+                    rows,
+                )
+            }
+            Self::SeqScans | Self::TableIndexScans => {
+                let rows = deltas
+                    .iter()
+                    .filter(|(_, counters)| counters[0] > 0.0)
+                    .map(|(labels, counters)| {
+                        json!({
+                            "name": labels[0],
+                            "count": counters[0] as i64,
+                            "per_sec": counters[0] / seconds,
+                        })
+                    })
+                    .collect();
+                (&["name", "count", "per_sec"], rows)
+            }
+            Self::IndexScans => {
+                let rows = deltas
+                    .iter()
+                    .filter(|(_, counters)| counters[0] > 0.0)
+                    .map(|(labels, counters)| {
+                        json!({
+                            "schemaname": labels[0],
+                            "table": labels[1],
+                            "index": labels[2],
+                            "index_scans": counters[0] as i64,
+                            "per_sec": counters[0] / seconds,
+                        })
+                    })
+                    .collect();
+                (
+                    &["schemaname", "table", "index", "index_scans", "per_sec"],
+                    rows,
+                )
+            }
+            Self::CacheHit => {
+                let rows = ["index hit rate", "table hit rate"]
+                    .iter()
+                    .map(|name| {
+                        let (hit, read) = deltas
+                            .iter()
+                            .filter(|(labels, _)| labels[0] == *name)
+                            .fold((0.0, 0.0), |(hit, read), (_, counters)| {
+                                (hit + counters[0], read + counters[1])
+                            });
+                        let ratio = (hit + read > 0.0).then(|| hit / (hit + read));
+                        json!({
+                            "name": name,
+                            "hit": hit as i64,
+                            "read": read as i64,
+                            "ratio": ratio,
+                        })
+                    })
+                    .collect();
+                (&["name", "hit", "read", "ratio"], rows)
+            }
+        };
+
+        let sort_column = match self {
+            Self::Calls => Some("calls"),
+            Self::Outliers => Some("exec_time_per_sec"),
+            Self::SeqScans | Self::TableIndexScans => Some("count"),
+            Self::IndexScans => Some("index_scans"),
+            Self::CacheHit => None,
+        };
+        if let Some(column) = sort_column {
+            rows.sort_by(|a, b| {
+                let value = |row: &Value| row[column].as_f64().unwrap_or_default();
+                value(b).partial_cmp(&value(a)).unwrap_or(Ordering::Equal)
+            });
+        }
+        rows.truncate(limit);
+
+        rates.output = QueryOutput {
+            description: self.description(&rates),
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            cells: rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .map(|header| cell(header, &row[*header]))
+                        .collect()
+                })
+                .collect(),
+            rows,
+        };
+        Ok(rates)
+    }
+
+    fn description(&self, rates: &Rates) -> String {
+        let mut description = format!(
+            "{}, over the last {}",
+            self.kind().description(),
+            humantime::format_duration(Duration::from_millis(rates.window.as_millis() as u64))
+        );
+        let changes = [
+            (rates.reset, "reset"),
+            (rates.appeared, "appeared"),
+            (rates.vanished, "vanished"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, change)| format!("{} {}", count, change))
+        .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            description.push_str(&format!(" ({})", changes.join(", ")));
+        }
+        description
+    }
+}
+
+fn cell(header: &str, value: &Value) -> String {
+    match (header, value) {
+        (_, Value::Null) => String::new(),
+        (_, Value::String(text)) => text.clone(),
+        ("mean_exec_time", value) => {
+            format_duration(&Duration::from_secs_f64(value.as_f64().unwrap_or_default()))
+        }
+        ("prop_exec_time", value) => format_percent(value.as_f64().unwrap_or_default()),
+        ("ratio", value) => format!("{:.4}", value.as_f64().unwrap_or_default()),
+        (header, value) if header.ends_with("_per_sec") => {
+            format!("{:.2}", value.as_f64().unwrap_or_default())
+        }
+        (_, value) => value.to_string(),
+    }
+}
+
+impl PgExtras {
+    /// Samples the counters of `kind` twice, `interval` apart, and reports per-second rates.
+    /// Fails for queries without cumulative counters, see `RateQuery::ALL`.
+    pub async fn rates(
+        &self,
+        kind: QueryKind,
+        params: HashMap<QueryParam, String>,
+        interval: Duration,
+    ) -> Result<Rates, PgExtrasError> {
+        let query = RateQuery::from_kind(kind).ok_or_else(|| {
+            PgExtrasError::Other(format!("Query '{}' has no cumulative counters", kind))
+        })?;
+
+        let before = query.sample(self, &params).await?;
+        tokio::time::sleep(interval).await;
+        let after = query.sample(self, &params).await?;
+        query.rates(&before, &after, &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seconds: u64, stats_reset: &str, rows: &[(i64, &str, f64)]) -> Sample {
+        Sample {
+            taken_at: Instant::now() + Duration::from_secs(seconds),
+            stats_reset: Some(stats_reset.to_string()),
+            rows: rows
+                .iter()
+                .map(|(key, name, count)| {
+                    let row = SampleRow {
+                        labels: vec![name.to_string()],
+                        counters: vec![*count],
+                    };
+                    (*key, row)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn subtracts_counters_by_key() {
+        let before = sample(
+            0,
+            "t0",
+            &[(1, "users", 100.0), (2, "orders", 50.0), (3, "old", 7.0)],
+        );
+        let after = sample(
+            10,
+            "t0",
+            &[(1, "users", 150.0), (2, "orders", 20.0), (4, "new", 5.0)],
+        );
+
+        let rates = RateQuery::SeqScans
+            .rates(&before, &after, &HashMap::new())
+            .unwrap();
+        assert_eq!((rates.reset, rates.appeared, rates.vanished), (1, 1, 1));
+
+        let counts = rates
+            .output
+            .rows
+            .iter()
+            .map(|row| {
+                (
+                    row["name"].as_str().unwrap(),
+                    row["count"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // orders was reset, so its counter since the reset is used
+        assert_eq!(counts, vec![("users", 50), ("orders", 20), ("new", 5)]);
+        assert!((rates.output.rows[0]["per_sec"].as_f64().unwrap() - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn treats_every_entry_as_reset_when_stats_were_reset() {
+        let before = sample(0, "t0", &[(1, "users", 100.0)]);
+        let after = sample(10, "t1", &[(1, "users", 120.0)]);
+
+        let rates = RateQuery::SeqScans
+            .rates(&before, &after, &HashMap::new())
+            .unwrap();
+        assert_eq!(rates.reset, 1);
+        assert_eq!(rates.output.rows[0]["count"], 120);
+        assert!(rates.output.description.contains("1 reset"));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    AllLocks, Bloat, Blocking, BuffercacheStats, BuffercacheUsage, CacheHit, Calls, Connections,
+    DbSettings, DuplicateIndexes, Extensions, IndexCacheHit, IndexScans, IndexSize, IndexUsage,
+    Indexes, Locks, LongRunningQueries, Mandelbrot, NullIndexes, Outliers, PgExtras, PgExtrasError,
+    Query, QueryParam, RecordsRank, SeqScans, Snapshot, SslUsed, TableCacheHit, TableIndexScans,
+    TableIndexesSize, TableSize, Tables, TotalIndexSize, TotalTableSize, UnusedIndexes,
+    VacuumStats,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Rows of a query with their types erased, ready to be rendered or serialized.
+#[derive(Debug, Clone)]
+pub struct QueryOutput {
+    pub description: String,
+    pub headers: Vec<String>,
+    /// Raw values, one JSON object per row.
+    pub rows: Vec<Value>,
+    /// Values formatted for display, in `headers` order.
+    pub cells: Vec<Vec<String>>,
+}
+
+impl QueryOutput {
+    pub fn new<T: Query>(items: &[T]) -> Self {
+        Self {
+            description: T::description(),
+            headers: row_cells(&T::headers()),
+            rows: items.iter().map(|item| item.to_json()).collect(),
+            cells: items.iter().map(|item| row_cells(&item.to_row())).collect(),
+        }
+    }
+
+    /// Prepends a `database` column, used when merging the rows of several databases.
+    pub fn with_database(mut self, database: &str) -> Self {
+        self.headers.insert(0, "database".to_string());
+        for row in &mut self.rows {
+            if let Value::Object(row) = row {
+                row.insert("database".to_string(), Value::from(database));
+            }
+        }
+        for cells in &mut self.cells {
+            cells.insert(0, database.to_string());
+        }
+        self
+    }
+
+    /// Appends the rows of the other outputs, which must have the same headers.
+    pub fn concat(outputs: Vec<QueryOutput>) -> Option<QueryOutput> {
+        let mut outputs = outputs.into_iter();
+        let mut merged = outputs.next()?;
+        for output in outputs {
+            merged.rows.extend(output.rows);
+            merged.cells.extend(output.cells);
+        }
+        Some(merged)
+    }
+}
+
+fn row_cells(row: &prettytable::Row) -> Vec<String> {
+    row.iter().map(|cell| cell.get_content()).collect()
+}
+
+macro_rules! query_kinds {
+    ($($kind:ident => $query:ty),* $(,)?) => {
+        /// Every built-in query. The CLI subcommands and the web UI are generated from it.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum QueryKind {
+            $($kind),*
+        }
+
+        impl QueryKind {
+            pub const ALL: &'static [QueryKind] = &[$(QueryKind::$kind),*];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$kind => <$query as Query>::name()),*
+                }
+            }
+
+            pub fn description(&self) -> String {
+                match self {
+                    $(Self::$kind => <$query as Query>::description()),*
+                }
+            }
+
+            pub fn params(&self) -> Vec<QueryParam> {
+                match self {
+                    $(Self::$kind => <$query as Query>::params()),*
+                }
+            }
+
+            pub fn required_extensions(&self) -> Vec<&'static str> {
+                match self {
+                    $(Self::$kind => <$query as Query>::required_extensions()),*
+                }
+            }
+
+            pub fn key_columns(&self) -> Vec<&'static str> {
+                match self {
+                    $(Self::$kind => <$query as Query>::key_columns()),*
+                }
+            }
+
+            pub fn headers(&self) -> Vec<String> {
+                match self {
+                    $(Self::$kind => row_cells(&<$query as Query>::headers())),*
+                }
+            }
+
+            /// Runs the query, params missing from the map use their default values.
+            pub async fn run(
+                &self,
+                client: &PgExtras,
+                params: HashMap<QueryParam, String>,
+            ) -> Result<QueryOutput, PgExtrasError> {
+                match self {
+                    $(Self::$kind => Ok(QueryOutput::new(
+                        &client.get_rows::<$query>(Some(params)).await?,
+                    ))),*
+                }
+            }
+
+            /// Rows captured in a snapshot, see `Snapshot::rows`.
+            pub fn from_snapshot(&self, snapshot: &Snapshot) -> Result<QueryOutput, PgExtrasError> {
+                match self {
+                    $(Self::$kind => Ok(QueryOutput::new(&snapshot.rows::<$query>()?))),*
+                }
+            }
+        }
+    };
+}
+
+query_kinds! {
+    AllLocks => AllLocks,
+    Bloat => Bloat,
+    Blocking => Blocking,
+    BuffercacheStats => BuffercacheStats,
+    BuffercacheUsage => BuffercacheUsage,
+    CacheHit => CacheHit,
+    Calls => Calls,
+    Connections => Connections,
+    DbSettings => DbSettings,
+    DuplicateIndexes => DuplicateIndexes,
+    Extensions => Extensions,
+    IndexCacheHit => IndexCacheHit,
+    IndexScans => IndexScans,
+    IndexSize => IndexSize,
+    IndexUsage => IndexUsage,
+    Indexes => Indexes,
+    Locks => Locks,
+    LongRunningQueries => LongRunningQueries,
+    Mandelbrot => Mandelbrot,
+    NullIndexes => NullIndexes,
+    Outliers => Outliers,
+    RecordsRank => RecordsRank,
+    SeqScans => SeqScans,
+    SslUsed => SslUsed,
+    TableCacheHit => TableCacheHit,
+    TableIndexScans => TableIndexScans,
+    TableIndexesSize => TableIndexesSize,
+    TableSize => TableSize,
+    Tables => Tables,
+    TotalIndexSize => TotalIndexSize,
+    TotalTableSize => TotalTableSize,
+    UnusedIndexes => UnusedIndexes,
+    VacuumStats => VacuumStats,
+}
+
+impl QueryKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+}
+
+impl std::fmt::Display for QueryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique_and_resolvable() {
+        for kind in QueryKind::ALL {
+            assert_eq!(QueryKind::from_name(kind.name()), Some(*kind));
+            assert!(!kind.description().is_empty());
+            assert!(!kind.headers().is_empty());
+        }
+        assert_eq!(QueryKind::ALL.len(), 33);
+        assert_eq!(QueryKind::from_name("diagnose"), None);
+    }
+
+    #[test]
+    fn output_keeps_raw_and_formatted_values() {
+        let output = QueryOutput::new(&[TotalTableSize {
+            name: "users".to_string(),
+            size: 2048,
+        }]);
+
+        assert_eq!(output.headers, vec!["name", "size"]);
+        assert_eq!(
+            output.rows,
+            vec![serde_json::json!({ "name": "users", "size": 2048 })]
+        );
+        assert_eq!(output.cells, vec![vec!["users", "2048 bytes"]]);
+    }
+
+    #[test]
+    fn merges_outputs_with_database_column() {
+        let output = |name: &str| {
+            QueryOutput::new(&[TotalTableSize {
+                name: name.to_string(),
+                size: 0,
+            }])
+        };
+        let merged = QueryOutput::concat(vec![
+            output("users").with_database("app"),
+            output("orders").with_database("shop"),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.headers, vec!["database", "name", "size"]);
+        assert_eq!(merged.rows[1]["database"], "shop");
+        assert_eq!(merged.cells[0], vec!["app", "users", "0 bytes"]);
+        assert!(QueryOutput::concat(vec![]).is_none());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{PgExtras, PgExtrasError, PgStatsVersion, QueryKind, ServerCapabilities};
+
+/// Statistics counters that can be reset, e.g. before measuring a change.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+/// use pg_extras::{PgExtras, StatsReset};
+///
+/// let client = PgExtras::connect().await?;
+/// let reset = StatsReset::Table("public.users".to_string());
+/// println!("Affects {:?}", reset.affected_queries());
+/// client.reset_stats(&reset).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatsReset {
+    /// `pg_stat_statements_reset`, scoping to a user or query requires pg_stat_statements 1.8.
+    Statements {
+        user: Option<String>,
+        queryid: Option<i64>,
+    },
+    /// `pg_stat_reset`, every table, index and function counter of the current database.
+    Database,
+    /// `pg_stat_reset_single_table_counters`, for a table or index of the current database.
+    Table(String),
+    /// `pg_stat_reset_shared`, counters shared by the whole cluster.
+    Shared(SharedStats),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SharedStats {
+    /// `pg_stat_bgwriter`, with the checkpoint counters before PostgreSQL 17.
+    Bgwriter,
+    /// `pg_stat_checkpointer`, PostgreSQL 17 and newer.
+    Checkpointer,
+    /// `pg_stat_io`, PostgreSQL 16 and newer.
+    Io,
+    /// `pg_stat_wal`, PostgreSQL 14 and newer.
+    Wal,
+}
+
+/// Queries reading the counters reset by `pg_stat_reset` and its single table variant.
+const TABLE_STATS_QUERIES: &[QueryKind] = &[
+    QueryKind::CacheHit,
+    QueryKind::IndexCacheHit,
+    QueryKind::TableCacheHit,
+    QueryKind::IndexScans,
+    QueryKind::IndexUsage,
+    QueryKind::SeqScans,
+    QueryKind::TableIndexScans,
+    QueryKind::UnusedIndexes,
+    QueryKind::RecordsRank,
+    QueryKind::VacuumStats,
+];
+
+impl SharedStats {
+    fn target(&self) -> &'static str {
+        match self {
+            Self::Bgwriter => "bgwriter",
+            Self::Checkpointer => "checkpointer",
+            Self::Io => "io",
+            Self::Wal => "wal",
+        }
+    }
+
+    fn min_server_major_version(&self) -> i32 {
+        match self {
+            Self::Bgwriter => 0,
+            Self::Checkpointer => 17,
+            Self::Io => 16,
+            Self::Wal => 14,
+        }
+    }
+}
+
+impl StatsReset {
+    /// What gets reset, e.g. `pg_stat_statements counters of user app`.
+    pub fn description(&self) -> String {
+        match self {
+            Self::Statements { user, queryid } => {
+                let mut description = "pg_stat_statements counters".to_string();
+                if let Some(queryid) = queryid {
+                    description.push_str(&format!(" of query {}", queryid));
+                }
+                if let Some(user) = user {
+                    description.push_str(&format!(" of user {}", user));
+                }
+                description
+            }
+            Self::Database => "every table and index counter of the current database".to_string(),
+            Self::Table(name) => format!("the counters of {}", name),
+            Self::Shared(stats) => format!("the shared {} counters", stats.target()),
+        }
+    }
+
+    /// Queries whose results start over after the reset. Shared counters aren't read by any.
+    pub fn affected_queries(&self) -> Vec<QueryKind> {
+        match self {
+            Self::Statements { .. } => vec![QueryKind::Calls, QueryKind::Outliers],
+            Self::Database | Self::Table(_) => TABLE_STATS_QUERIES.to_vec(),
+            Self::Shared(
+                SharedStats::Bgwriter
+                | SharedStats::Checkpointer
+                | SharedStats::Io
+                | SharedStats::Wal,
+            ) => vec![],
+        }
+    }
+
+    /// Fails when the server lacks the extension or version the reset needs.
+    pub fn check(&self, capabilities: &ServerCapabilities) -> Result<(), PgExtrasError> {
+        match self {
+            Self::Statements { .. } if !capabilities.has_extension("pg_stat_statements") => {
+                Err(PgExtrasError::ExtensionMissing {
+                    query: "pg_stat_statements_reset".to_string(),
+                    extension: "pg_stat_statements".to_string(),
+                    source: None,
+                })
+            }
+            Self::Statements { user, queryid }
+                if (user.is_some() || queryid.is_some())
+                    && capabilities.pg_stats_version() == PgStatsVersion::Legacy =>
+            {
+                Err(PgExtrasError::Other(
+                    "Resetting the statements of a user or query requires pg_stat_statements 1.8 or newer".to_string(),
+                ))
+            }
+            Self::Shared(stats)
+                if capabilities.server_major_version() < stats.min_server_major_version() =>
+            {
+                Err(PgExtrasError::Other(format!(
+                    "Resetting the shared {} counters requires PostgreSQL {} or newer",
+                    stats.target(),
+                    stats.min_server_major_version()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl PgExtras {
+    pub async fn reset_stats(&self, reset: &StatsReset) -> Result<(), PgExtrasError> {
+        reset.check(self.capabilities())?;
+
+        let (function, query) = match reset {
+            StatsReset::Statements {
+                user: None,
+                queryid: None,
+            } => (
+                "pg_stat_statements_reset",
+                sqlx::query("SELECT pg_stat_statements_reset()"),
+            ),
+            StatsReset::Statements { user, queryid } => {
+                // 0 resets every user or query, an unknown user is rejected
+                let sql = "SELECT pg_stat_statements_reset(
+                    CASE WHEN $1::text IS NULL THEN 0 ELSE $1::regrole::oid END, 0, coalesce($2, 0)
+                )";
+                (
+                    "pg_stat_statements_reset",
+                    sqlx::query(sql).bind(user.clone()).bind(*queryid),
+                )
+            }
+            StatsReset::Database => ("pg_stat_reset", sqlx::query("SELECT pg_stat_reset()")),
+            StatsReset::Table(name) => (
+                "pg_stat_reset_single_table_counters",
+                sqlx::query("SELECT pg_stat_reset_single_table_counters($1::regclass)")
+                    .bind(name.clone()),
+            ),
+            StatsReset::Shared(stats) => (
+                "pg_stat_reset_shared",
+                sqlx::query("SELECT pg_stat_reset_shared($1)").bind(stats.target()),
+            ),
+        };
+
+        query
+            .execute(self.pool())
+            .await
+            .map_err(|e| PgExtrasError::query_failed(function, e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use crate::diagnose::config::DiagnoseConfig;
+use crate::diagnose::run::{run_checks, CheckResult, DiagnoseSource};
+use crate::{
+    DbSettings, PgExtras, PgExtrasError, Query, QueryKind, QueryParam, ServerCapabilities,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Format version written to new snapshots. Bump it on incompatible changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Results of every query captured at one point in time, to be inspected offline.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+/// use pg_extras::{render_table, PgExtras, Snapshot, TableSize};
+///
+/// let client = PgExtras::connect().await?;
+/// Snapshot::capture(&client).await?.save("snap.json")?;
+///
+/// let snapshot = Snapshot::from_path("snap.json")?;
+/// render_table(snapshot.rows::<TableSize>()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    #[serde(with = "humantime_serde")]
+    pub created_at: SystemTime,
+    /// Value of the `server_version` setting, e.g. `16.4 (Debian 16.4-1.pgdg120+2)`.
+    pub server_version: String,
+    pub pg_stat_statements_version: Option<String>,
+    pub capabilities: ServerCapabilities,
+    pub db_settings: Vec<DbSettings>,
+    /// Raw rows of each query by name, as produced by `Query::to_json`.
+    pub queries: BTreeMap<String, SnapshotQuery>,
+    /// Queries that were not captured, with the reason.
+    pub skipped: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotQuery {
+    /// Values of the query params used for the capture.
+    pub params: BTreeMap<String, String>,
+    pub rows: Vec<Value>,
+}
+
+impl Snapshot {
+    /// Runs every registered query, skipping the ones whose extension is missing.
+    /// Failing queries are recorded in `skipped` instead of aborting the capture.
+    pub async fn capture(client: &PgExtras) -> Result<Self, PgExtrasError> {
+        let capabilities = client.capabilities();
+        let server_version = sqlx::query_scalar("SELECT current_setting('server_version')")
+            .fetch_one(client.pool())
+            .await
+            .map_err(|e| PgExtrasError::query_failed("server_version", e))?;
+
+        let mut queries = BTreeMap::new();
+        let mut skipped = BTreeMap::new();
+        for kind in QueryKind::ALL {
+            let missing_extension = kind
+                .required_extensions()
+                .into_iter()
+                .find(|extension| !capabilities.has_extension(extension));
+            if let Some(extension) = missing_extension {
+                skipped.insert(
+                    kind.name().to_string(),
+                    format!("extension '{}' is not installed", extension),
+                );
+                continue;
+            }
+
+            let params = capture_params(kind, client);
+            match kind.run(client, params.clone()).await {
+                Ok(output) => {
+                    let params = params
+                        .into_iter()
+                        .map(|(param, value)| (param.name().to_string(), value))
+                        .collect();
+                    let rows = output.rows;
+                    queries.insert(kind.name().to_string(), SnapshotQuery { params, rows });
+                }
+                Err(e) => {
+                    skipped.insert(kind.name().to_string(), e.to_string());
+                }
+            }
+        }
+
+        Ok(Self {
+            version: SNAPSHOT_VERSION,
+            created_at: SystemTime::now(),
+            server_version,
+            pg_stat_statements_version: capabilities
+                .extension_version("pg_stat_statements")
+                .map(|version| version.to_string()),
+            capabilities: capabilities.clone(),
+            db_settings: client.db_settings().await?,
+            queries,
+            skipped,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PgExtrasError> {
+        let value: Value = serde_json::from_str(json)
+            .map_err(|e| PgExtrasError::InvalidSnapshot(e.to_string()))?;
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+        if version == 0 || version > SNAPSHOT_VERSION as u64 {
+            return Err(PgExtrasError::InvalidSnapshot(format!(
+                "unsupported version {}, expected at most {}",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+
+        serde_json::from_value(value).map_err(|e| PgExtrasError::InvalidSnapshot(e.to_string()))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, PgExtrasError> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| {
+            PgExtrasError::InvalidSnapshot(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        Self::from_json(&json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PgExtrasError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json() + "\n")
+            .map_err(|e| PgExtrasError::Other(format!("Cannot write {}: {}", path.display(), e)))
+    }
+
+    /// Rows of a captured query, deserialized into its struct.
+    pub fn rows<T: Query>(&self) -> Result<Vec<T>, PgExtrasError> {
+        let Some(query) = self.queries.get(T::name()) else {
+            let reason = self
+                .skipped
+                .get(T::name())
+                .map_or("it was not captured", |reason| reason.as_str());
+            return Err(PgExtrasError::InvalidSnapshot(format!(
+                "query '{}' is not available: {}",
+                T::name(),
+                reason
+            )));
+        };
+
+        query
+            .rows
+            .iter()
+            .map(|row| serde_json::from_value(row.clone()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| PgExtrasError::InvalidSnapshot(format!("query '{}': {}", T::name(), e)))
+    }
+
+    /// Runs the diagnose checks against the captured rows.
+    pub async fn diagnose(
+        &self,
+        diagnose_config: &DiagnoseConfig,
+    ) -> Result<Vec<CheckResult>, PgExtrasError> {
+        run_checks(&SnapshotSource { snapshot: self }, diagnose_config).await
+    }
+}
+
+/// Default params, except for `null_indexes` which uses the diagnose threshold so that
+/// `diagnose` gives the same results offline.
+fn capture_params(kind: &QueryKind, client: &PgExtras) -> HashMap<QueryParam, String> {
+    kind.params()
+        .into_iter()
+        .map(|param| {
+            let value = match param {
+                QueryParam::Schema => client.default_schema(),
+                QueryParam::MinRelationSizeMb if *kind == QueryKind::NullIndexes => client
+                    .diagnose_config()
+                    .null_indexes_min_size_mb
+                    .to_string(),
+                param => param.default_value(),
+            };
+            (param, value)
+        })
+        .collect()
+}
+
+struct SnapshotSource<'a> {
+    snapshot: &'a Snapshot,
+}
+
+impl DiagnoseSource for SnapshotSource<'_> {
+    fn capabilities(&self) -> &ServerCapabilities {
+        &self.snapshot.capabilities
+    }
+
+    /// Params are ignored, the rows were captured with the ones recorded in the snapshot.
+    async fn rows<T: Query>(
+        &self,
+        _params: HashMap<QueryParam, String>,
+    ) -> Result<Vec<T>, PgExtrasError> {
+        self.snapshot.rows()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnose::run::{Check, Severity};
+    use crate::{LongRunningQueries, TableSize};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn snapshot() -> Snapshot {
+        let query = |rows| SnapshotQuery {
+            params: BTreeMap::new(),
+            rows,
+        };
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: SystemTime::UNIX_EPOCH,
+            server_version: "16.4".to_string(),
+            pg_stat_statements_version: None,
+            capabilities: ServerCapabilities::default(),
+            db_settings: vec![],
+            queries: BTreeMap::from([
+                (
+                    "table_size".to_string(),
+                    query(vec![
+                        json!({ "name": "users", "size": 8192, "schema": "public" }),
+                    ]),
+                ),
+                (
+                    "long_running_queries".to_string(),
+                    query(vec![
+                        json!({ "pid": 42, "duration": 1.5, "query": "SELECT 1" }),
+                    ]),
+                ),
+            ]),
+            skipped: BTreeMap::from([(
+                "calls".to_string(),
+                "extension 'pg_stat_statements' is not installed".to_string(),
+            )]),
+        }
+    }
+
+    #[test]
+    fn round_trips_rows_into_query_structs() {
+        let snapshot = Snapshot::from_json(&snapshot().to_json()).unwrap();
+        assert!(snapshot
+            .to_json()
+            .contains("\"created_at\": \"1970-01-01T00:00:00Z\""));
+
+        let tables = snapshot.rows::<TableSize>().unwrap();
+        assert_eq!(tables[0].name, "users");
+        assert_eq!(tables[0].size, 8192);
+
+        let queries = snapshot.rows::<LongRunningQueries>().unwrap();
+        assert_eq!(queries[0].duration, Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn diagnoses_checks_without_rows_as_critical() {
+        let mut snapshot = snapshot();
+        for name in [
+            "cache_hit",
+            "unused_indexes",
+            "null_indexes",
+            "duplicate_indexes",
+        ] {
+            snapshot.queries.insert(
+                name.to_string(),
+                SnapshotQuery {
+                    params: BTreeMap::new(),
+                    rows: vec![],
+                },
+            );
+        }
+
+        // bloat was not captured, the other checks are still reported
+        let results = snapshot.diagnose(&DiagnoseConfig::default()).await.unwrap();
+        assert_eq!(results.len(), 6);
+        let bloat = results
+            .iter()
+            .find(|result| result.check == Check::Bloat)
+            .unwrap();
+        assert_eq!(bloat.severity, Severity::Critical);
+        assert!(
+            bloat.message.starts_with("Check failed:"),
+            "{}",
+            bloat.message
+        );
+        assert!(bloat.threshold.is_some());
+        assert!(results
+            .iter()
+            .filter(|result| result.check != Check::Bloat)
+            .all(|result| result.severity != Severity::Critical));
+    }
+
+    #[test]
+    fn reports_skipped_queries_and_versions() {
+        let err = snapshot().rows::<crate::Calls>().unwrap_err();
+        assert!(err.to_string().contains("pg_stat_statements"));
+
+        let mut future = serde_json::to_value(snapshot()).unwrap();
+        future["version"] = json!(SNAPSHOT_VERSION + 1);
+        assert!(matches!(
+            Snapshot::from_json(&future.to_string()),
+            Err(PgExtrasError::InvalidSnapshot(_))
+        ));
+    }
+}
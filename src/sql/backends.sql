@@ -0,0 +1,24 @@
+/* Client and replication backends, with what protects them from being signalled. */
+
+SELECT
+  a.pid,
+  coalesce(a.usename::text, '') AS usename,
+  coalesce(a.application_name, '') AS application_name,
+  coalesce(a.state, '') AS state,
+  coalesce(a.backend_type, '') AS backend_type,
+  (extract(epoch FROM a.backend_start) * 1000000)::bigint AS backend_start,
+  now() - coalesce(
+    CASE WHEN a.state = 'active' THEN a.query_start ELSE a.state_change END,
+    a.backend_start
+  ) AS duration,
+  coalesce(a.query, '') AS query,
+  a.pid = pg_backend_pid() OR coalesce(a.application_name = $1, false) AS own,
+  coalesce(r.rolsuper, false) AS superuser,
+  a.backend_type IN ('walsender', 'logical replication worker') AS replication,
+  cardinality(pg_blocking_pids(a.pid)) = 0 AND EXISTS (
+    SELECT 1 FROM pg_stat_activity w WHERE a.pid = ANY(pg_blocking_pids(w.pid))
+  ) AS blocking_root
+FROM pg_stat_activity a
+LEFT JOIN pg_roles r ON r.oid = a.usesysid
+WHERE a.usesysid IS NOT NULL
+ORDER BY duration DESC;
@@ -17,4 +17,4 @@ JOIN pg_catalog.pg_locks kl
 ON bl.transactionid = kl.transactionid AND bl.pid != kl.pid
 WHERE NOT bl.granted
 ORDER BY blocked_duration DESC
-LIMIT %{limit};
+LIMIT $1;
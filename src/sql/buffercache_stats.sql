@@ -2,9 +2,9 @@
 
 SELECT
   c.relname,
-  pg_size_pretty(count(*) * 8192) AS buffered,
-  round(100.0 * count(*) / (SELECT setting FROM pg_settings WHERE name = 'shared_buffers')::integer, 1) AS buffer_percent,
-  round(100.0 * count(*) * 8192 / pg_table_size(c.oid), 1) AS percent_of_relation
+  count(*) * 8192 AS buffered,
+  round(100.0 * count(*) / (SELECT setting FROM pg_settings WHERE name = 'shared_buffers')::integer, 1)::float8 AS buffer_percent,
+  round(100.0 * count(*) * 8192 / nullif(pg_table_size(c.oid), 0), 1)::float8 AS percent_of_relation
 FROM pg_class c
 INNER JOIN pg_buffercache b ON b.relfilenode = c.relfilenode
 INNER JOIN pg_database d ON (b.reldatabase = d.oid AND d.datname = current_database())
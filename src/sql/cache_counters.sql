@@ -0,0 +1,15 @@
+/* Cumulative buffer hits and reads by indexrelid and relid. */
+
+SELECT indexrelid::bigint AS key,
+  'index hit rate' AS name,
+  coalesce(idx_blks_hit, 0)::float8 AS hit,
+  coalesce(idx_blks_read, 0)::float8 AS read
+FROM pg_statio_user_indexes
+WHERE schemaname = $1
+UNION ALL
+SELECT relid::bigint AS key,
+  'table hit rate' AS name,
+  coalesce(heap_blks_hit, 0)::float8 AS hit,
+  coalesce(heap_blks_read, 0)::float8 AS read
+FROM pg_statio_user_tables
+WHERE schemaname = $1;
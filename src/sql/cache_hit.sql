@@ -2,12 +2,12 @@
 
 SELECT
   'index hit rate' AS name,
-  (sum(idx_blks_hit)) / nullif(sum(idx_blks_hit + idx_blks_read),0) AS ratio
+  (sum(idx_blks_hit)) / nullif(sum(idx_blks_hit + idx_blks_read),0)::float8 AS ratio
 FROM pg_statio_user_indexes
-WHERE schemaname = '%{schema}'
+WHERE schemaname = $1
 UNION ALL
 SELECT
  'table hit rate' AS name,
-  sum(heap_blks_hit) / nullif(sum(heap_blks_hit) + sum(heap_blks_read),0) AS ratio
+  sum(heap_blks_hit) / nullif(sum(heap_blks_hit) + sum(heap_blks_read),0)::float8 AS ratio
 FROM pg_statio_user_tables
-WHERE schemaname = '%{schema}';
+WHERE schemaname = $1;
@@ -5,13 +5,10 @@ SELECT
   idx_blks_hit AS buffer_hits,
   idx_blks_read AS block_reads,
   idx_blks_hit + idx_blks_read AS total_read,
-  CASE (idx_blks_hit + idx_blks_read)::float
-    WHEN 0 THEN 'Insufficient data'
-    ELSE (idx_blks_hit / (idx_blks_hit + idx_blks_read)::float)::text
-  END ratio
+  idx_blks_hit / nullif(idx_blks_hit + idx_blks_read, 0)::float AS ratio
 FROM
   pg_statio_user_tables
 WHERE
-  schemaname = '%{schema}'
+  schemaname = $1
 ORDER BY
   idx_blks_hit / (idx_blks_hit + idx_blks_read + 1)::float DESC;
@@ -0,0 +1,9 @@
+/* Cumulative scan counters by indexrelid. */
+
+SELECT indexrelid::bigint AS key,
+       schemaname::text AS schema,
+       relname::text AS table,
+       indexrelname::text AS index,
+       idx_scan::float8 AS idx_scan
+FROM pg_stat_user_indexes
+WHERE schemaname = $1;
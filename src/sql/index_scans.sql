@@ -4,10 +4,10 @@ SELECT
   schemaname,
   relname AS table,
   indexrelname AS index,
-  pg_size_pretty(pg_relation_size(i.indexrelid)) AS index_size,
+  pg_relation_size(i.indexrelid) AS index_size,
   idx_scan as index_scans
 FROM pg_stat_user_indexes ui
 JOIN pg_index i ON ui.indexrelid = i.indexrelid
-WHERE schemaname = '%{schema}'
+WHERE schemaname = $1
 ORDER BY pg_relation_size(i.indexrelid) / nullif(idx_scan, 0) DESC NULLS FIRST,
 pg_relation_size(i.indexrelid) DESC;
@@ -1,7 +1,7 @@
 /* The size of indexes, descending by size. */
 
 SELECT c.relname AS name,
-  pg_size_pretty(sum(c.relpages::bigint*8192)::bigint) AS size,
+  sum(c.relpages::bigint*8192)::bigint AS size,
   n.nspname as schema
 FROM pg_class c
 LEFT JOIN pg_namespace n ON (n.oid = c.relnamespace)
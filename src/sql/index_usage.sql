@@ -2,13 +2,13 @@
 
 SELECT relname,
    CASE idx_scan
-     WHEN 0 THEN 'Insufficient data'
-     ELSE (100 * idx_scan / (seq_scan + idx_scan))::text
+     WHEN 0 THEN NULL
+     ELSE 100 * idx_scan / (seq_scan + idx_scan)
    END percent_of_times_index_used,
    n_live_tup rows_in_table
  FROM
    pg_stat_user_tables
  WHERE
-   schemaname = '%{schema}'
+   schemaname = $1
  ORDER BY
    n_live_tup DESC;
@@ -3,15 +3,12 @@
 SELECT
     c.oid,
     c.relname AS index,
-    pg_size_pretty(pg_relation_size(c.oid)) AS index_size,
+    pg_relation_size(c.oid) AS index_size,
     i.indisunique AS unique,
     a.attname AS indexed_column,
     s.tablename AS table,
-    CASE s.null_frac
-        WHEN 0 THEN ''
-        ELSE to_char(s.null_frac * 100, '999.00%%')
-    END AS null_frac,
-    pg_size_pretty((pg_relation_size(c.oid) * s.null_frac)::bigint) AS expected_saving,
+    s.null_frac::float8 AS null_frac,
+    (pg_relation_size(c.oid) * s.null_frac)::bigint AS expected_saving,
     n.nspname as schema
 FROM
     pg_class c
@@ -31,6 +28,6 @@ WHERE
     -- Exclude indexes without null_frac ratio
     AND coalesce(s.null_frac, 0) != 0
     -- Larger than threshold
-    AND pg_relation_size(c.oid) > %{min_relation_size_mb} * 1024 ^ 2
+    AND pg_relation_size(c.oid) > $1 * 1024 ^ 2
 ORDER BY
   pg_relation_size(c.oid) * s.null_frac DESC;
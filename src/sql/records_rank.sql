@@ -6,6 +6,6 @@ SELECT
 FROM
   pg_stat_user_tables
 WHERE
-  schemaname = '%{schema}'
+  schemaname = $1
 ORDER BY
   n_live_tup DESC;
@@ -5,5 +5,5 @@ SELECT relname AS name,
 FROM
   pg_stat_user_tables
 WHERE
-  schemaname = '%{schema}'
+  schemaname = $1
 ORDER BY seq_scan DESC;
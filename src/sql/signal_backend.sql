@@ -0,0 +1,12 @@
+/* Signals a listed backend, unless it is gone, was replaced or is protected now. */
+
+SELECT
+  CASE WHEN $5 THEN pg_terminate_backend(a.pid) ELSE pg_cancel_backend(a.pid) END
+FROM pg_stat_activity a
+LEFT JOIN pg_roles r ON r.oid = a.usesysid
+WHERE a.pid = $1
+  AND (extract(epoch FROM a.backend_start) * 1000000)::bigint = $2
+  AND a.pid <> pg_backend_pid()
+  AND a.application_name IS DISTINCT FROM $6
+  AND ($3 OR NOT coalesce(r.rolsuper, false))
+  AND ($4 OR a.backend_type NOT IN ('walsender', 'logical replication worker'));
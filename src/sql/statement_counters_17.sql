@@ -0,0 +1,9 @@
+/* Cumulative pg_stat_statements counters by queryid. */
+
+SELECT coalesce(queryid, 0) AS key,
+min(query) AS query,
+sum(calls)::float8 AS calls,
+sum(total_exec_time)::float8 AS exec_time,
+sum(shared_blk_read_time + shared_blk_write_time)::float8 AS sync_io_time
+FROM pg_stat_statements WHERE userid = (SELECT usesysid FROM pg_user WHERE usename = current_user LIMIT 1)
+GROUP BY queryid;
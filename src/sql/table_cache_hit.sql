@@ -5,10 +5,7 @@ SELECT
   heap_blks_hit AS buffer_hits,
   heap_blks_read AS block_reads,
   heap_blks_hit + heap_blks_read AS total_read,
-  CASE (heap_blks_hit + heap_blks_read)::float
-    WHEN 0 THEN 'Insufficient data'
-    ELSE (heap_blks_hit / (heap_blks_hit + heap_blks_read)::float)::text
-  END ratio
+  heap_blks_hit / nullif(heap_blks_hit + heap_blks_read, 0)::float AS ratio
 FROM
   pg_statio_user_tables
 WHERE
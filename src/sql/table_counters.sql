@@ -0,0 +1,9 @@
+/* Cumulative scan counters by table relid. */
+
+SELECT relid::bigint AS key,
+       schemaname::text AS schema,
+       relname::text AS name,
+       seq_scan::float8 AS seq_scan,
+       coalesce(idx_scan, 0)::float8 AS idx_scan
+FROM pg_stat_user_tables
+WHERE schemaname = $1;
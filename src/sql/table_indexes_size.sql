@@ -1,7 +1,7 @@
 /* Total size of all the indexes on each table, descending by size. */
 
 SELECT c.relname AS table,
-  pg_size_pretty(pg_indexes_size(c.oid)) AS index_size
+  pg_indexes_size(c.oid) AS index_size
 FROM pg_class c
 LEFT JOIN pg_namespace n ON (n.oid = c.relnamespace)
 WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
@@ -1,3 +1,3 @@
 /* List all the tables. */
 
-select relname as tablename, schemaname from pg_statio_user_tables where schemaname = '%{schema}';
+select relname as tablename, schemaname from pg_statio_user_tables where schemaname = $1;
@@ -1,6 +1,6 @@
 /* Total size of all indexes in MB. */
 
-SELECT pg_size_pretty(sum(c.relpages::bigint*8192)::bigint) AS size
+SELECT coalesce(sum(c.relpages::bigint*8192), 0)::bigint AS size
 FROM pg_class c
 LEFT JOIN pg_namespace n ON (n.oid = c.relnamespace)
 WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
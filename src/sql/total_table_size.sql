@@ -1,7 +1,7 @@
 /* Size of the tables (including indexes), descending by size. */
 
 SELECT c.relname AS name,
-  pg_size_pretty(pg_total_relation_size(c.oid)) AS size
+  pg_total_relation_size(c.oid) AS size
 FROM pg_class c
 LEFT JOIN pg_namespace n ON (n.oid = c.relnamespace)
 WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
@@ -0,0 +1,141 @@
+mod state;
+mod ui;
+
+use crate::{PgExtras, PgExtrasError};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::DefaultTerminal;
+use state::TopState;
+use std::collections::HashMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How often pending key presses are checked while waiting for the next refresh.
+const INPUT_POLL: Duration = Duration::from_millis(100);
+
+enum Action {
+    None,
+    Refresh,
+    Quit,
+}
+
+/// Full-screen view of the database activity, refreshing the selected query every `refresh`.
+/// Restores the terminal on exit, also when a query fails.
+pub async fn run(client: &PgExtras, refresh: Duration) -> Result<(), PgExtrasError> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(client, &mut terminal, refresh).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    client: &PgExtras,
+    terminal: &mut DefaultTerminal,
+    refresh: Duration,
+) -> Result<(), PgExtrasError> {
+    let mut state = TopState::default();
+    let mut refreshed_at: Option<Instant> = None;
+
+    loop {
+        let view = state.view;
+        if refreshed_at.is_none_or(|at| at.elapsed() >= refresh) {
+            let output = state.kind().run(client, HashMap::new()).await;
+            state.set_output(output);
+            state.statement = None;
+            refreshed_at = Some(Instant::now());
+        }
+        if state.show_detail {
+            load_statement(client, &mut state).await;
+        }
+
+        terminal
+            .draw(|frame| ui::draw(frame, &mut state))
+            .map_err(terminal_error)?;
+
+        if event::poll(INPUT_POLL).map_err(terminal_error)? {
+            if let Event::Key(key) = event::read().map_err(terminal_error)? {
+                match handle_key(&mut state, key) {
+                    Action::Quit => return Ok(()),
+                    Action::Refresh => refreshed_at = None,
+                    Action::None => {}
+                }
+            }
+        }
+        if state.view != view {
+            refreshed_at = None;
+        }
+    }
+}
+
+/// Looks up the current statement of the selected backend, once per backend and refresh.
+async fn load_statement(client: &PgExtras, state: &mut TopState) {
+    let Some(pid) = state.selected_pid() else {
+        return;
+    };
+    if state
+        .statement
+        .as_ref()
+        .is_some_and(|(loaded, _)| *loaded == pid)
+    {
+        return;
+    }
+
+    let statement = sqlx::query_scalar("SELECT query FROM pg_stat_activity WHERE pid = $1")
+        .bind(pid)
+        .fetch_optional(client.pool())
+        .await;
+    match statement {
+        Ok(statement) => state.statement = Some((pid, statement)),
+        Err(e) => {
+            state.error = Some(PgExtrasError::query_failed("pg_stat_activity", e).to_string())
+        }
+    }
+}
+
+fn handle_key(state: &mut TopState, key: KeyEvent) -> Action {
+    if key.kind != KeyEventKind::Press {
+        return Action::None;
+    }
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        return Action::Quit;
+    }
+
+    if state.editing_filter {
+        match key.code {
+            KeyCode::Enter => state.editing_filter = false,
+            KeyCode::Esc => state.clear_filter(),
+            KeyCode::Backspace => state.pop_filter(),
+            KeyCode::Char(c) => state.push_filter(c),
+            _ => {}
+        }
+        return Action::None;
+    }
+
+    match key.code {
+        KeyCode::Char('q') => return Action::Quit,
+        KeyCode::Esc if state.show_detail => state.show_detail = false,
+        KeyCode::Esc if !state.filter.is_empty() => state.clear_filter(),
+        KeyCode::Esc => return Action::Quit,
+        KeyCode::Char('r') => return Action::Refresh,
+        KeyCode::Tab => state.next_view(),
+        KeyCode::BackTab => state.previous_view(),
+        KeyCode::Char(c @ '1'..='9') => state.set_view(c as usize - '1' as usize),
+        KeyCode::Down | KeyCode::Char('j') => state.move_selection(1),
+        KeyCode::Up | KeyCode::Char('k') => state.move_selection(-1),
+        KeyCode::PageDown => state.move_selection(20),
+        KeyCode::PageUp => state.move_selection(-20),
+        KeyCode::Home => state.select_first(),
+        KeyCode::End => state.select_last(),
+        KeyCode::Char('s') => state.cycle_sort(),
+        KeyCode::Char('S') => state.reverse_sort(),
+        KeyCode::Char('/') => state.editing_filter = true,
+        KeyCode::Enter => state.show_detail = !state.show_detail,
+        KeyCode::Char('J') => state.detail_scroll = state.detail_scroll.saturating_add(1),
+        KeyCode::Char('K') => state.detail_scroll = state.detail_scroll.saturating_sub(1),
+        _ => {}
+    }
+    Action::None
+}
+
+fn terminal_error(e: io::Error) -> PgExtrasError {
+    PgExtrasError::Other(format!("Terminal error: {}", e))
+}
@@ -0,0 +1,287 @@
+use crate::{PgExtrasError, QueryKind, QueryOutput};
+use ratatui::widgets::TableState;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+/// A tab of the `top` view, showing one query.
+pub(crate) struct View {
+    pub group: &'static str,
+    pub kind: QueryKind,
+}
+
+pub(crate) const VIEWS: &[View] = &[
+    View {
+        group: "sessions",
+        kind: QueryKind::LongRunningQueries,
+    },
+    View {
+        group: "sessions",
+        kind: QueryKind::Connections,
+    },
+    View {
+        group: "locks",
+        kind: QueryKind::Blocking,
+    },
+    View {
+        group: "locks",
+        kind: QueryKind::Locks,
+    },
+    View {
+        group: "statements",
+        kind: QueryKind::Calls,
+    },
+    View {
+        group: "statements",
+        kind: QueryKind::Outliers,
+    },
+    View {
+        group: "cache",
+        kind: QueryKind::CacheHit,
+    },
+];
+
+/// Columns holding a backend pid, used to look up the full statement of a row.
+const PID_COLUMNS: &[&str] = &["pid", "blocked_pid"];
+
+/// Everything shown by `top` apart from the terminal itself.
+#[derive(Default)]
+pub(crate) struct TopState {
+    pub view: usize,
+    pub output: Option<QueryOutput>,
+    pub error: Option<String>,
+    pub refreshed_at: Option<SystemTime>,
+    /// Sorted column and whether the order is descending.
+    pub sort: Option<(usize, bool)>,
+    pub filter: String,
+    pub editing_filter: bool,
+    pub table: TableState,
+    pub show_detail: bool,
+    pub detail_scroll: u16,
+    /// Full statement of the backend shown in the detail pane.
+    pub statement: Option<(i32, Option<String>)>,
+}
+
+impl TopState {
+    pub fn kind(&self) -> QueryKind {
+        VIEWS[self.view].kind
+    }
+
+    pub fn set_view(&mut self, view: usize) {
+        if view == self.view || view >= VIEWS.len() {
+            return;
+        }
+        *self = Self {
+            view,
+            show_detail: self.show_detail,
+            ..Self::default()
+        };
+    }
+
+    pub fn next_view(&mut self) {
+        self.set_view((self.view + 1) % VIEWS.len());
+    }
+
+    pub fn previous_view(&mut self) {
+        self.set_view((self.view + VIEWS.len() - 1) % VIEWS.len());
+    }
+
+    pub fn set_output(&mut self, output: Result<QueryOutput, PgExtrasError>) {
+        match output {
+            Ok(output) => {
+                self.output = Some(output);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.refreshed_at = Some(SystemTime::now());
+        self.clamp_selection();
+    }
+
+    /// Indexes of the output rows matching the filter, in display order.
+    pub fn visible_rows(&self) -> Vec<usize> {
+        let Some(output) = &self.output else {
+            return vec![];
+        };
+
+        let filter = self.filter.to_lowercase();
+        let mut rows = (0..output.rows.len())
+            .filter(|index| {
+                filter.is_empty()
+                    || output.cells[*index]
+                        .iter()
+                        .any(|cell| cell.to_lowercase().contains(&filter))
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((column, descending)) = self.sort {
+            let header = &output.headers[column];
+            rows.sort_by(|a, b| {
+                let ordering = compare_values(&output.rows[*a][header], &output.rows[*b][header]);
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+        }
+        rows
+    }
+
+    pub fn selected_row(&self) -> Option<&Value> {
+        let index = *self.visible_rows().get(self.table.selected()?)?;
+        self.output.as_ref().map(|output| &output.rows[index])
+    }
+
+    pub fn selected_pid(&self) -> Option<i32> {
+        let row = self.selected_row()?;
+        PID_COLUMNS
+            .iter()
+            .find_map(|column| row.get(*column)?.as_i64())
+            .map(|pid| pid as i32)
+    }
+
+    /// Sorts by the next column, descending first, then goes back to the query order.
+    pub fn cycle_sort(&mut self) {
+        let columns = self
+            .output
+            .as_ref()
+            .map_or(0, |output| output.headers.len());
+        self.sort = match self.sort {
+            None if columns > 0 => Some((0, true)),
+            Some((column, descending)) if column + 1 < columns => Some((column + 1, descending)),
+            _ => None,
+        };
+    }
+
+    pub fn reverse_sort(&mut self) {
+        if let Some((_, descending)) = &mut self.sort {
+            *descending = !*descending;
+        }
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.clamp_selection();
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.clamp_selection();
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter.clear();
+        self.editing_filter = false;
+        self.clamp_selection();
+    }
+
+    /// Moves the selection by `offset` rows, staying within the visible rows.
+    pub fn move_selection(&mut self, offset: isize) {
+        let rows = self.visible_rows().len();
+        if rows == 0 {
+            self.table.select(None);
+            return;
+        }
+        let selected = self.table.selected().unwrap_or(0) as isize + offset;
+        self.table
+            .select(Some(selected.clamp(0, rows as isize - 1) as usize));
+        self.detail_scroll = 0;
+    }
+
+    pub fn select_first(&mut self) {
+        self.table.select(None);
+        self.move_selection(0);
+    }
+
+    pub fn select_last(&mut self) {
+        self.move_selection(self.visible_rows().len() as isize);
+    }
+
+    fn clamp_selection(&mut self) {
+        self.move_selection(0);
+    }
+}
+
+/// Numbers by value, other values by their text. Nulls come first.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (a, b) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state() -> TopState {
+        let rows = vec![
+            json!({ "pid": 10, "duration": 2.5, "query": "SELECT * FROM users" }),
+            json!({ "pid": 9, "duration": 30.0, "query": "VACUUM orders" }),
+            json!({ "pid": 11, "duration": 0.1, "query": "SELECT * FROM orders" }),
+        ];
+        let output = QueryOutput {
+            description: String::new(),
+            headers: vec!["pid".into(), "duration".into(), "query".into()],
+            cells: rows
+                .iter()
+                .map(|row| {
+                    vec![
+                        row["pid"].to_string(),
+                        row["duration"].to_string(),
+                        row["query"].as_str().unwrap().to_string(),
+                    ]
+                })
+                .collect(),
+            rows,
+        };
+
+        let mut state = TopState::default();
+        state.set_output(Ok(output));
+        state
+    }
+
+    #[test]
+    fn sorts_and_filters_rows() {
+        let mut state = state();
+        assert_eq!(state.visible_rows(), vec![0, 1, 2]);
+
+        state.cycle_sort();
+        assert_eq!(state.sort, Some((0, true)));
+        state.cycle_sort();
+        assert_eq!(state.visible_rows(), vec![1, 0, 2]);
+        state.reverse_sort();
+        assert_eq!(state.visible_rows(), vec![2, 0, 1]);
+
+        "ORDERS".chars().for_each(|c| state.push_filter(c));
+        assert_eq!(state.visible_rows(), vec![2, 1]);
+        state.clear_filter();
+        assert_eq!(state.visible_rows().len(), 3);
+    }
+
+    #[test]
+    fn keeps_selection_within_visible_rows() {
+        let mut state = state();
+        assert_eq!(state.selected_pid(), Some(10));
+
+        state.move_selection(10);
+        assert_eq!(state.selected_pid(), Some(11));
+
+        "vacuum".chars().for_each(|c| state.push_filter(c));
+        assert_eq!(state.table.selected(), Some(0));
+        assert_eq!(state.selected_pid(), Some(9));
+
+        state.set_output(Err(PgExtrasError::Other("timeout".to_string())));
+        assert_eq!(state.error.as_deref(), Some("timeout"));
+        // the previous rows stay visible
+        assert_eq!(state.selected_pid(), Some(9));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::tui::state::{TopState, VIEWS};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, Tabs, Wrap};
+use ratatui::Frame;
+use serde_json::Value;
+
+const MAX_COLUMN_WIDTH: usize = 60;
+
+pub(crate) fn draw(frame: &mut Frame, state: &mut TopState) {
+    let detail_height = match state.show_detail {
+        true => Constraint::Percentage(40),
+        false => Constraint::Length(0),
+    };
+    let [tabs, table, detail, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        detail_height,
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_tabs(frame, state, tabs);
+    draw_table(frame, state, table);
+    if state.show_detail {
+        draw_detail(frame, state, detail);
+    }
+    draw_footer(frame, state, footer);
+}
+
+fn draw_tabs(frame: &mut Frame, state: &TopState, area: Rect) {
+    let titles = VIEWS
+        .iter()
+        .enumerate()
+        .map(|(index, view)| format!("{} {}:{}", index + 1, view.group, view.kind.name()));
+    let tabs = Tabs::new(titles)
+        .select(state.view)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_widget(tabs, area);
+}
+
+fn draw_table(frame: &mut Frame, state: &mut TopState, area: Rect) {
+    let mut title = state.kind().description();
+    if let Some(refreshed_at) = state.refreshed_at {
+        title = format!(
+            "{} at {}",
+            title,
+            humantime::format_rfc3339_seconds(refreshed_at)
+        );
+    }
+    let mut block = Block::default().borders(Borders::ALL).title(title);
+    if let Some(error) = &state.error {
+        block = block.title_bottom(Line::styled(
+            error.as_str(),
+            Style::default().fg(Color::Red),
+        ));
+    }
+
+    let Some(output) = &state.output else {
+        frame.render_widget(Paragraph::new("Loading...").block(block), area);
+        return;
+    };
+
+    let header = Row::new(output.headers.iter().enumerate().map(|(column, header)| {
+        let arrow = match state.sort {
+            Some((sorted, true)) if sorted == column => " ▼",
+            Some((sorted, false)) if sorted == column => " ▲",
+            _ => "",
+        };
+        Cell::from(format!("{}{}", header, arrow))
+    }))
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let visible_rows = state.visible_rows();
+    let cells = visible_rows
+        .iter()
+        .map(|index| {
+            output.cells[*index]
+                .iter()
+                .map(|cell| one_line(cell))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let widths = output
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            let width = cells
+                .iter()
+                .map(|row| row[column].chars().count())
+                .chain([header.chars().count() + 2])
+                .max()
+                .unwrap_or_default();
+            Constraint::Max(width.min(MAX_COLUMN_WIDTH) as u16)
+        })
+        .collect::<Vec<_>>();
+
+    let table = Table::new(cells.into_iter().map(Row::new), widths)
+        .header(header)
+        .block(block)
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, &mut state.table);
+}
+
+/// Every column of the selected row, with the full statement of its backend when it has one.
+fn draw_detail(frame: &mut Frame, state: &TopState, area: Rect) {
+    let mut lines = vec![];
+    if let (Some(row), Some(output)) = (state.selected_row(), &state.output) {
+        for header in &output.headers {
+            let value = match &row[header] {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{}: ", header),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ]));
+        }
+    }
+
+    match (&state.statement, state.selected_pid()) {
+        (Some((pid, Some(statement))), Some(selected)) if *pid == selected => {
+            lines.push(Line::default());
+            lines.push(Line::styled(
+                format!("Current statement of {}:", pid),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+            lines.extend(statement.lines().map(|line| Line::raw(line.to_string())));
+        }
+        (Some((pid, None)), Some(selected)) if *pid == selected => {
+            lines.push(Line::default());
+            lines.push(Line::raw(format!("Backend {} is gone", pid)));
+        }
+        _ => {}
+    }
+
+    let detail = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Detail"))
+        .wrap(Wrap { trim: false })
+        .scroll((state.detail_scroll, 0));
+    frame.render_widget(detail, area);
+}
+
+fn draw_footer(frame: &mut Frame, state: &TopState, area: Rect) {
+    let footer = match state.editing_filter {
+        true => format!("Filter: {}█  (enter: apply, esc: clear)", state.filter),
+        false => {
+            let filter = match state.filter.is_empty() {
+                true => String::new(),
+                false => format!("[filter: {}] ", state.filter),
+            };
+            format!(
+                "{}q: quit  tab/1-{}: view  ↑↓: select  s/S: sort/reverse  /: filter  enter: detail  J/K: scroll detail  r: refresh",
+                filter,
+                VIEWS.len()
+            )
+        }
+    };
+    frame.render_widget(
+        Paragraph::new(footer).style(Style::default().fg(Color::DarkGray)),
+        area,
+    );
+}
+
+/// Query texts span several lines, the table shows them on one.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,252 @@
+use crate::diff::{index_rows, row_key};
+use crate::{PgExtras, PgExtrasError, QueryKind, QueryOutput, QueryParam};
+use prettytable::{Cell, Row as TableRow, Table};
+use sqlx::postgres::PgConnection;
+use sqlx::Connection;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Runs a query repeatedly on a single connection, comparing each result with the previous one.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), pg_extras::PgExtrasError> {
+/// use pg_extras::{PgExtras, QueryKind, Watch};
+/// use std::collections::HashMap;
+///
+/// let client = PgExtras::connect().await?;
+/// let mut watch = Watch::new(&client, QueryKind::Blocking, HashMap::new()).await?;
+/// loop {
+///     let tick = watch.tick().await?;
+///     tick.table().printstd();
+///     tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Watch {
+    client: PgExtras,
+    kind: QueryKind,
+    params: HashMap<QueryParam, String>,
+    /// Backend of the watch connection, to cancel its query from another one.
+    backend_pid: Option<i32>,
+    previous: Option<QueryOutput>,
+}
+
+/// Result of one run of a watched query.
+#[derive(Debug, Clone)]
+pub struct WatchTick {
+    pub taken_at: SystemTime,
+    pub output: QueryOutput,
+    /// Status of each row of `output`, compared with the previous tick.
+    pub statuses: Vec<RowStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowStatus {
+    Unchanged,
+    /// The row was not in the previous tick. Every row of the first tick is unchanged.
+    New,
+    /// Headers of the columns whose value changed.
+    Changed(Vec<String>),
+}
+
+impl Watch {
+    pub async fn new(
+        client: &PgExtras,
+        kind: QueryKind,
+        params: HashMap<QueryParam, String>,
+    ) -> Result<Self, PgExtrasError> {
+        let options = (*client.pool().connect_options()).clone();
+        Ok(Self {
+            client: client.with_single_connection(options).await?,
+            kind,
+            params,
+            backend_pid: None,
+            previous: None,
+        })
+    }
+
+    /// Runs the query once and compares the rows with the previous tick.
+    pub async fn tick(&mut self) -> Result<WatchTick, PgExtrasError> {
+        // The connection can be replaced by the pool, so its backend is checked on every tick.
+        self.backend_pid = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(self.client.pool())
+            .await
+            .map(Some)
+            .map_err(|e| PgExtrasError::query_failed(self.kind.name(), e))?;
+
+        let output = self.kind.run(&self.client, self.params.clone()).await?;
+        let statuses = row_statuses(&self.kind, self.previous.as_ref(), &output);
+        self.previous = Some(output.clone());
+
+        Ok(WatchTick {
+            taken_at: SystemTime::now(),
+            output,
+            statuses,
+        })
+    }
+
+    /// Cancels the query of an interrupted `tick` on the server, using a separate connection.
+    /// Returns whether a query was cancelled.
+    pub async fn cancel(&self) -> Result<bool, PgExtrasError> {
+        let Some(pid) = self.backend_pid else {
+            return Ok(false);
+        };
+
+        let mut conn = PgConnection::connect_with(&self.client.pool().connect_options())
+            .await
+            .map_err(|e| PgExtrasError::DbConnectionError(format!("{}", e)))?;
+        let cancelled = sqlx::query_scalar("SELECT pg_cancel_backend($1)")
+            .bind(pid)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| PgExtrasError::query_failed("pg_cancel_backend", e))?;
+        let _ = conn.close().await;
+        Ok(cancelled)
+    }
+
+    pub async fn close(self) {
+        self.client.pool().close().await;
+    }
+}
+
+impl WatchTick {
+    /// Table with new rows in green and changed values in yellow.
+    pub fn table(&self) -> Table {
+        let mut table = Table::new();
+        table.set_titles(TableRow::new(vec![Cell::new(&format!(
+            "{} at {}",
+            self.output.description,
+            humantime::format_rfc3339_seconds(self.taken_at)
+        ))
+        .style_spec(&format!("H{}", self.output.headers.len()))]));
+        table.add_row(TableRow::new(
+            self.output
+                .headers
+                .iter()
+                .map(|header| Cell::new(header))
+                .collect(),
+        ));
+
+        for (cells, status) in self.output.cells.iter().zip(&self.statuses) {
+            table.add_row(TableRow::new(
+                cells
+                    .iter()
+                    .zip(&self.output.headers)
+                    .map(|(cell, header)| match status {
+                        RowStatus::New => Cell::new(cell).style_spec("Fg"),
+                        RowStatus::Changed(columns) if columns.contains(header) => {
+                            Cell::new(cell).style_spec("Fy")
+                        }
+                        _ => Cell::new(cell),
+                    })
+                    .collect(),
+            ));
+        }
+        table
+    }
+}
+
+/// Matches rows by the query key columns, or by their whole content for queries without.
+pub fn row_statuses(
+    kind: &QueryKind,
+    previous: Option<&QueryOutput>,
+    current: &QueryOutput,
+) -> Vec<RowStatus> {
+    let Some(previous) = previous else {
+        return vec![RowStatus::Unchanged; current.rows.len()];
+    };
+
+    let key_columns = kind.key_columns();
+    if key_columns.is_empty() {
+        return current
+            .rows
+            .iter()
+            .map(|row| match previous.rows.contains(row) {
+                true => RowStatus::Unchanged,
+                false => RowStatus::New,
+            })
+            .collect();
+    }
+
+    let previous_rows = index_rows(&previous.rows, &key_columns);
+    current
+        .rows
+        .iter()
+        .map(|row| match previous_rows.get(&row_key(row, &key_columns)) {
+            None => RowStatus::New,
+            Some(previous_row) => {
+                let changed = current
+                    .headers
+                    .iter()
+                    .filter(|header| previous_row.get(header) != row.get(header))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                match changed.is_empty() {
+                    true => RowStatus::Unchanged,
+                    false => RowStatus::Changed(changed),
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn output(rows: Vec<Value>) -> QueryOutput {
+        QueryOutput {
+            description: String::new(),
+            headers: vec![
+                "pid".to_string(),
+                "duration".to_string(),
+                "query".to_string(),
+            ],
+            cells: vec![],
+            rows,
+        }
+    }
+
+    #[test]
+    fn compares_rows_by_key() {
+        let previous = output(vec![
+            json!({ "pid": 1, "duration": 1.0, "query": "SELECT 1" }),
+            json!({ "pid": 2, "duration": 5.0, "query": "SELECT 2" }),
+        ]);
+        let current = output(vec![
+            json!({ "pid": 1, "duration": 1.0, "query": "SELECT 1" }),
+            json!({ "pid": 2, "duration": 7.0, "query": "SELECT 2" }),
+            json!({ "pid": 3, "duration": 0.5, "query": "SELECT 3" }),
+        ]);
+
+        let kind = QueryKind::LongRunningQueries;
+        assert_eq!(
+            row_statuses(&kind, Some(&previous), &current),
+            vec![
+                RowStatus::Unchanged,
+                RowStatus::Changed(vec!["duration".to_string()]),
+                RowStatus::New
+            ]
+        );
+        assert_eq!(
+            row_statuses(&kind, None, &current),
+            vec![RowStatus::Unchanged; 3]
+        );
+    }
+
+    #[test]
+    fn compares_whole_rows_without_key() {
+        let previous = output(vec![json!({ "pid": 1, "duration": 1.0, "query": "a" })]);
+        let current = output(vec![
+            json!({ "pid": 1, "duration": 1.0, "query": "a" }),
+            json!({ "pid": 1, "duration": 2.0, "query": "a" }),
+        ]);
+
+        assert_eq!(
+            row_statuses(&QueryKind::Mandelbrot, Some(&previous), &current),
+            vec![RowStatus::Unchanged, RowStatus::New]
+        );
+    }
+}
@@ -1,36 +1,53 @@
-use crate::diagnose;
-use crate::diagnose::run::CheckResult;
+use super::{query_names, ProfileParam};
+use crate::diagnose::report::by_severity;
+use crate::diagnose::run::{health_score, CheckResult, Severity};
 use crate::web::routes::AppState;
+use crate::PgExtras;
 use askama_axum::Template;
-use axum::{extract::State, response::IntoResponse};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
 use eyre::Result;
 use reqwest::StatusCode;
-use sqlx::{Pool, Postgres};
 
 #[derive(Template)]
 #[template(path = "home.html")]
 pub struct HomeTemplate {
     pub alert: Option<String>,
+    pub profile: String,
+    pub profile_names: Vec<String>,
     pub query_name: String,
+    pub query_names: Vec<String>,
     pub checks_result: Vec<CheckResult>,
+    pub health_score: u8,
     pub version: String,
 }
 
-pub async fn home(State(state): State<AppState>) -> impl IntoResponse {
-    let checks_result = match get_data(&state.pool).await {
-        Ok(data) => data,
+pub async fn home(State(state): State<AppState>, query: Query<ProfileParam>) -> impl IntoResponse {
+    let (profile, client) = match state.client(query.0.profile.as_deref()) {
+        Ok(selected) => selected,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+
+    let checks_result = match get_data(client).await {
+        Ok(data) => by_severity(&data),
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
     HomeTemplate {
         version: env!("CARGO_PKG_VERSION").to_string(),
         alert: state.alert.lock().unwrap().clone(),
+        profile: profile.to_string(),
+        profile_names: state.profile_names(),
         query_name: "diagnose".to_string(),
+        query_names: query_names(client),
+        health_score: health_score(&checks_result),
         checks_result,
     }
     .into_response()
 }
 
-async fn get_data(pool: &Pool<Postgres>) -> Result<Vec<CheckResult>> {
-    Ok(diagnose(pool).await?)
+async fn get_data(client: &PgExtras) -> Result<Vec<CheckResult>> {
+    Ok(client.diagnose().await?)
 }
@@ -1,2 +1,24 @@
+use crate::{PgExtras, QueryKind};
+use serde::Deserialize;
+
 pub mod home;
 pub mod queries;
+
+/// Options of the query selector, in registry order followed by the custom queries.
+pub fn query_names(client: &PgExtras) -> Vec<String> {
+    QueryKind::ALL
+        .iter()
+        .map(|kind| kind.name().to_string())
+        .chain(
+            client
+                .custom_queries()
+                .iter()
+                .map(|query| query.name.clone()),
+        )
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProfileParam {
+    pub profile: Option<String>,
+}
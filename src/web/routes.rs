@@ -1,21 +1,49 @@
 use axum::{routing::get, Router};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 
 use super::controllers;
-use sqlx::PgPool;
+use crate::PgExtras;
 use tower_http::services::ServeDir;
 
 #[derive(Clone, Debug)]
 pub struct AppState {
-    pub pool: PgPool,
+    /// Clients by profile name, selected with the `profile` query param.
+    pub clients: Arc<BTreeMap<String, PgExtras>>,
+    pub default_profile: String,
     pub alert: Arc<Mutex<Option<String>>>,
 }
 
-pub async fn app(pool: PgPool) -> Router {
+impl AppState {
+    /// The requested profile and its client, `None` selects the default profile.
+    pub fn client(&self, profile: Option<&str>) -> eyre::Result<(&str, &PgExtras)> {
+        let name = profile.unwrap_or(&self.default_profile);
+        match self.clients.get_key_value(name) {
+            Some((name, client)) => Ok((name, client)),
+            None => eyre::bail!("Invalid profile: {}", name),
+        }
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.clients.keys().cloned().collect()
+    }
+}
+
+pub async fn app(client: PgExtras) -> Router {
+    let clients = BTreeMap::from([("default".to_string(), client)]);
+    app_with_profiles(clients, "default".to_string()).await
+}
+
+/// Serves several databases, e.g. every profile of a `Config`.
+pub async fn app_with_profiles(
+    clients: BTreeMap<String, PgExtras>,
+    default_profile: String,
+) -> Router {
     let state = AppState {
-        pool,
+        clients: Arc::new(clients),
+        default_profile,
         alert: Arc::new(Mutex::new(None)),
     };
 